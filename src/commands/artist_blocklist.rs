@@ -0,0 +1,227 @@
+use poise::send_reply;
+use tracing::error;
+
+use crate::{
+    commands::tags::autocomplete_tags,
+    query::{is_metatag, tag_name},
+    Context, Error,
+};
+
+/// Gets or changes the artists whose posts are never sent in the guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("show", "add", "remove")
+)]
+pub async fn artist_blocklist(ctx: Context<'_>) -> Result<(), Error> {
+    show_guild_blocklist(ctx).await
+}
+
+/// Shows the artists whose posts are never sent in the guild
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    show_guild_blocklist(ctx).await
+}
+
+/// Adds artists whose posts are never sent in the guild
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "The artist tags to block"]
+    #[autocomplete = "autocomplete_tags"]
+    artists: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let added = match parse_artists(ctx, &artists).await? {
+        Some(added) => added,
+        None => return Ok(()),
+    };
+
+    // looking up the artists takes a request to e621
+    ctx.defer_ephemeral().await?;
+    let warnings = artist_warnings(ctx, &added).await;
+
+    let mut blocklist = ctx.data().guild_blocked_artists(guild).await;
+    add_artists(&mut blocklist, added);
+    let content = blocklist_message(&blocklist, &warnings);
+    ctx.data().set_guild_blocked_artists(guild, blocklist).await;
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Removes artists from the blocklist of the guild
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "The artist tags to no longer block"] artists: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let mut blocklist = ctx.data().guild_blocked_artists(guild).await;
+    remove_artists(&mut blocklist, &artists);
+    let content = blocklist_message(&blocklist, &[]);
+    ctx.data().set_guild_blocked_artists(guild, blocklist).await;
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Gets or changes the artists blocked in every guild (can only be used by bot owners)
+#[poise::command(
+    prefix_command,
+    slash_command,
+    owners_only,
+    subcommands("global_show", "global_add", "global_remove")
+)]
+pub async fn global_artist_blocklist(ctx: Context<'_>) -> Result<(), Error> {
+    show_global_blocklist(ctx).await
+}
+
+/// Shows the artists whose posts are never sent in any guild
+#[poise::command(prefix_command, slash_command, owners_only, rename = "show")]
+pub async fn global_show(ctx: Context<'_>) -> Result<(), Error> {
+    show_global_blocklist(ctx).await
+}
+
+/// Adds artists whose posts are never sent in any guild
+#[poise::command(prefix_command, slash_command, owners_only, rename = "add")]
+pub async fn global_add(
+    ctx: Context<'_>,
+    #[description = "The artist tags to block"]
+    #[autocomplete = "autocomplete_tags"]
+    artists: String,
+) -> Result<(), Error> {
+    let added = match parse_artists(ctx, &artists).await? {
+        Some(added) => added,
+        None => return Ok(()),
+    };
+
+    ctx.defer_ephemeral().await?;
+    let warnings = artist_warnings(ctx, &added).await;
+
+    let mut blocklist = ctx.data().blocked_artists();
+    add_artists(&mut blocklist, added);
+    let content = blocklist_message(&blocklist, &warnings);
+    ctx.data().set_blocked_artists(blocklist);
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Removes artists from the blocklist of every guild
+#[poise::command(prefix_command, slash_command, owners_only, rename = "remove")]
+pub async fn global_remove(
+    ctx: Context<'_>,
+    #[description = "The artist tags to no longer block"] artists: String,
+) -> Result<(), Error> {
+    let mut blocklist = ctx.data().blocked_artists();
+    remove_artists(&mut blocklist, &artists);
+    let content = blocklist_message(&blocklist, &[]);
+    ctx.data().set_blocked_artists(blocklist);
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Replies with the blocklist of the guild
+async fn show_guild_blocklist(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let content = format_blocklist(&ctx.data().guild_blocked_artists(guild).await);
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Replies with the blocklist of every guild
+async fn show_global_blocklist(ctx: Context<'_>) -> Result<(), Error> {
+    let content = format_blocklist(&ctx.data().blocked_artists());
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Splits the artists of a command into tag names.
+///
+/// Replies and returns `None` if one of them is a metatag
+async fn parse_artists(ctx: Context<'_>, artists: &str) -> Result<Option<Vec<String>>, Error> {
+    let artists: Vec<String> = artists
+        .split_ascii_whitespace()
+        .map(|tag| tag_name(tag).to_lowercase())
+        .collect();
+
+    if let Some(metatag) = artists.iter().find(|tag| is_metatag(tag)) {
+        let content = format!(
+            "Blocklist has not been changed: metatags like `{}` can not be blocked",
+            metatag
+        );
+        send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+        return Ok(None);
+    }
+
+    Ok(Some(artists))
+}
+
+/// Warns about artists which are not artist tags on e621
+async fn artist_warnings(ctx: Context<'_>, artists: &[String]) -> Vec<String> {
+    let names: Vec<&str> = artists.iter().map(|artist| artist.as_str()).collect();
+    match ctx.data().tag_api().tags(&names).await {
+        Ok(tags) => artists
+            .iter()
+            .filter_map(|artist| match tags.iter().find(|tag| &tag.name == artist) {
+                Some(tag) if tag.is_artist() => None,
+                Some(_) => Some(format!("`{}` is not an artist tag", artist)),
+                None => Some(format!("`{}` does not exist", artist)),
+            })
+            .collect(),
+        Err(err) => {
+            error!("Could not look up tags: {}", err);
+            vec!["Could not check if the artists exist".to_string()]
+        }
+    }
+}
+
+fn add_artists(blocklist: &mut Vec<String>, added: Vec<String>) {
+    for artist in added {
+        if !blocklist.contains(&artist) {
+            blocklist.push(artist);
+        }
+    }
+}
+
+fn remove_artists(blocklist: &mut Vec<String>, artists: &str) {
+    let removed: Vec<String> = artists
+        .split_ascii_whitespace()
+        .map(|tag| tag_name(tag).to_lowercase())
+        .collect();
+    blocklist.retain(|artist| !removed.contains(artist));
+}
+
+fn blocklist_message(blocklist: &[String], warnings: &[String]) -> String {
+    let mut content = format!("New blocklist: {}", format_blocklist(blocklist));
+    for warning in warnings {
+        content.push_str(&format!("\nWarning: {}", warning));
+    }
+    content
+}
+
+fn format_blocklist(blocklist: &[String]) -> String {
+    if blocklist.is_empty() {
+        "Blocklist is empty.".to_string()
+    } else {
+        blocklist
+            .iter()
+            .map(|artist| format!("`{}`", artist))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
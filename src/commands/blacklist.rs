@@ -0,0 +1,158 @@
+use poise::send_reply;
+use tracing::error;
+
+use crate::{
+    commands::tags::autocomplete_tags,
+    query::{is_metatag, tag_name},
+    Context, Error,
+};
+
+/// Gets or changes the tags excluded in every channel of the guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("show", "add", "remove")
+)]
+pub async fn blacklist(ctx: Context<'_>) -> Result<(), Error> {
+    show_blacklist(ctx).await
+}
+
+/// Shows the tags excluded in every channel of the guild
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    show_blacklist(ctx).await
+}
+
+/// Adds tags to the blacklist of the guild
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "The tags to exclude in every channel"]
+    #[autocomplete = "autocomplete_tags"]
+    tags: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let added: Vec<String> = tags
+        .split_ascii_whitespace()
+        .map(|tag| tag_name(tag).to_lowercase())
+        .collect();
+
+    if let Some(metatag) = added.iter().find(|tag| is_metatag(tag)) {
+        let content = format!(
+            "Blacklist has not been changed: metatags like `{}` can not be blacklisted",
+            metatag
+        );
+        send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    // looking up tags takes a few requests to e621
+    ctx.defer_ephemeral().await?;
+
+    let tag_api = ctx.data().tag_api();
+    let mut warnings = Vec::new();
+
+    let names: Vec<&str> = added.iter().map(|tag| tag.as_str()).collect();
+    let added: Vec<String> = match tag_api.aliases(&names).await {
+        Ok(aliases) => added
+            .iter()
+            .map(
+                |tag| match aliases.iter().find(|alias| &alias.antecedent_name == tag) {
+                    Some(alias) => {
+                        warnings.push(format!(
+                            "`{}` is an alias of `{}`",
+                            alias.antecedent_name, alias.consequent_name
+                        ));
+                        alias.consequent_name.clone()
+                    }
+                    None => tag.clone(),
+                },
+            )
+            .collect(),
+        Err(err) => {
+            error!("Could not look up aliases: {}", err);
+            warnings.push("Could not check for aliases".to_string());
+            added
+        }
+    };
+
+    match tag_api.unknown_tags(&added).await {
+        Ok(unknown_tags) => {
+            for unknown_tag in unknown_tags {
+                warnings.push(format!("`{}` does not exist", unknown_tag.name));
+            }
+        }
+        Err(err) => {
+            error!("Could not look up tags: {}", err);
+            warnings.push("Could not check if the tags exist".to_string());
+        }
+    }
+
+    let mut blacklist = ctx.data().blacklist(guild).await;
+    for tag in added {
+        if !blacklist.contains(&tag) {
+            blacklist.push(tag);
+        }
+    }
+
+    let mut content = format!("New blacklist: {}", format_blacklist(&blacklist));
+    for warning in warnings {
+        content.push_str(&format!("\nWarning: {}", warning));
+    }
+
+    ctx.data().set_blacklist(guild, blacklist).await;
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Removes tags from the blacklist of the guild
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "The tags to no longer exclude"] tags: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let removed: Vec<String> = tags
+        .split_ascii_whitespace()
+        .map(|tag| tag_name(tag).to_lowercase())
+        .collect();
+
+    let mut blacklist = ctx.data().blacklist(guild).await;
+    blacklist.retain(|tag| !removed.contains(tag));
+
+    let content = format!("New blacklist: {}", format_blacklist(&blacklist));
+
+    ctx.data().set_blacklist(guild, blacklist).await;
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Replies with the blacklist of the guild
+async fn show_blacklist(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let content = format_blacklist(&ctx.data().blacklist(guild).await);
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+fn format_blacklist(blacklist: &[String]) -> String {
+    if blacklist.is_empty() {
+        "Blacklist is empty.".to_string()
+    } else {
+        blacklist
+            .iter()
+            .map(|tag| format!("`{}`", tag))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
@@ -0,0 +1,47 @@
+use poise::send_reply;
+
+use crate::{configuration::DuplicateCheck, Context, Error};
+
+/// Gets or sets where the channel looks for files which have been posted before, to skip them
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn duplicates(
+    ctx: Context<'_>,
+    #[description = "Where to look for files posted before"] check: Option<DuplicateCheck>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let current_duplicate_check = ctx.data().duplicate_check(guild, channel).await;
+
+    let content = if let Some(new_duplicate_check) = check {
+        let content = if let Some(current_duplicate_check) = current_duplicate_check {
+            format!(
+                "Old duplicate check: {}\nNew duplicate check: {}",
+                current_duplicate_check, new_duplicate_check
+            )
+        } else {
+            format!(
+                "Old duplicate check is not set.\nNew duplicate check: {}",
+                new_duplicate_check
+            )
+        };
+
+        ctx.data()
+            .set_duplicate_check(guild, channel, new_duplicate_check)
+            .await;
+
+        content
+    } else if let Some(current_duplicate_check) = current_duplicate_check {
+        current_duplicate_check.to_string()
+    } else {
+        "Duplicate check is not set.\n".to_string()
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use poise::send_reply;
+
+use crate::{configuration::EmbedLayout, Context, Error};
+
+/// Gets or sets how much information is shown with posts in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn embed(
+    ctx: Context<'_>,
+    #[description = "How much to show"] layout: Option<EmbedLayout>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let current_layout = ctx.data().embed_layout(guild, channel).await;
+
+    let content = if let Some(new_layout) = layout {
+        let content = if let Some(current_layout) = current_layout {
+            format!(
+                "Old embed layout: {}\nNew embed layout: {}",
+                current_layout, new_layout
+            )
+        } else {
+            format!(
+                "Old embed layout is not set.\nNew embed layout: {}",
+                new_layout
+            )
+        };
+
+        ctx.data()
+            .set_embed_layout(guild, channel, new_layout)
+            .await;
+
+        content
+    } else if let Some(current_layout) = current_layout {
+        current_layout.to_string()
+    } else {
+        "Embed layout is not set.\n".to_string()
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use poise::{send_reply, serenity_prelude::GuildChannel};
+
+use crate::{Context, Error};
+
+/// Gets or changes the channel errors of the guild are reported in
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("show", "set", "clear")
+)]
+pub async fn log_channel(ctx: Context<'_>) -> Result<(), Error> {
+    show_log_channel(ctx).await
+}
+
+/// Shows the channel errors of the guild are reported in
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    show_log_channel(ctx).await
+}
+
+/// Reports the errors of every channel of the guild in one channel
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "The channel to report errors in"] channel: GuildChannel,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    ctx.data().set_log_channel(guild, Some(channel.id)).await;
+
+    let content = format!("Errors are reported in <#{}> now.", channel.id);
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Reports errors in the channels they occur in again
+#[poise::command(prefix_command, slash_command, required_permissions = "MANAGE_GUILD")]
+pub async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    ctx.data().set_log_channel(guild, None).await;
+
+    let content = "Errors are reported in the channels they occur in now.";
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+async fn show_log_channel(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let content = match ctx.data().log_channel(guild).await {
+        Some(log_channel) => format!("Errors are reported in <#{}>.", log_channel),
+        None => {
+            "Log channel is not set. Errors are reported in the channels they occur in.".to_string()
+        }
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
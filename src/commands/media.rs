@@ -0,0 +1,47 @@
+use poise::send_reply;
+
+use crate::{configuration::MediaMode, Context, Error};
+
+/// Gets or sets the kinds of files posted in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn media(
+    ctx: Context<'_>,
+    #[description = "Kinds of files to post"] media: Option<MediaMode>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let current_media_mode = ctx.data().media_mode(guild, channel).await;
+
+    let content = if let Some(new_media_mode) = media {
+        let content = if let Some(current_media_mode) = current_media_mode {
+            format!(
+                "Old media mode: {}\nNew media mode: {}",
+                current_media_mode, new_media_mode
+            )
+        } else {
+            format!(
+                "Old media mode is not set.\nNew media mode: {}",
+                new_media_mode
+            )
+        };
+
+        ctx.data()
+            .set_media_mode(guild, channel, new_media_mode)
+            .await;
+
+        content
+    } else if let Some(current_media_mode) = current_media_mode {
+        current_media_mode.to_string()
+    } else {
+        "Media mode is not set.\n".to_string()
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
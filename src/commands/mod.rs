@@ -1,8 +1,23 @@
+pub mod artist_blocklist;
+pub mod blacklist;
+pub mod duplicates;
+pub mod embed;
+pub mod log_channel;
+pub mod media;
 pub mod nsfw;
+pub mod pool;
+pub mod post_format;
 pub mod timeout_mode;
+pub mod post_now;
+pub mod preset;
+pub mod preview;
 pub mod register;
+pub mod rotation;
 pub mod shutdown;
+pub mod source;
+pub mod spoiler_tags;
 pub mod start;
+pub mod status;
 pub mod stop;
 pub mod tags;
 pub mod timeout;
@@ -0,0 +1,180 @@
+use poise::send_reply;
+
+use crate::{
+    commands::tags::autocomplete_tags, configuration::PoolProgress, pool_api::PoolInfo, Context,
+    Error,
+};
+
+/// Gets or changes the e621 pool whose pages are posted in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    subcommands("show", "read", "random", "stop")
+)]
+pub async fn pool(ctx: Context<'_>) -> Result<(), Error> {
+    show_pool(ctx).await
+}
+
+/// Shows the pool whose pages are posted in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    show_pool(ctx).await
+}
+
+/// Posts the pages of a pool in the channel, one per timeout
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn read(
+    ctx: Context<'_>,
+    #[description = "ID of the pool"] id: u64,
+    #[description = "Page to start at, the first one by default"] page: Option<usize>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    // looking up the pool takes a request to e621
+    ctx.defer_ephemeral().await?;
+    let pool = ctx.data().pool(guild, channel, id).await?;
+
+    let page = page.unwrap_or(1);
+    if page == 0 || page > pool.post_ids.len() {
+        let content = format!(
+            "{} only has pages 1 to {}",
+            pool.title(),
+            pool.post_ids.len()
+        );
+        send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let pool_progress = PoolProgress {
+        pool_id: pool.id,
+        page: page - 1,
+        random_tags: None,
+    };
+    let content = progress_message(&pool, &pool_progress);
+    ctx.data()
+        .set_pool_progress(guild, channel, Some(pool_progress))
+        .await;
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Posts the pages of random pools with posts matching the tags, one pool after the other
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn random(
+    ctx: Context<'_>,
+    #[description = "Tags of the pools, the tags of the channel by default"]
+    #[autocomplete = "autocomplete_tags"]
+    tags: Option<String>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let tags = match tags {
+        Some(tags) => tags.split_whitespace().map(str::to_string).collect(),
+        // the channel tags usually exclude comics, which would leave no pools
+        None => ctx
+            .data()
+            .tags(guild, channel)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|tag| tag != "-comic")
+            .collect(),
+    };
+
+    // looking up the pool takes requests to e621
+    ctx.defer_ephemeral().await?;
+    let content = match ctx.data().read_random_pool(guild, channel, tags).await {
+        Ok(pool) => match ctx.data().pool_progress(guild, channel).await {
+            Some(pool_progress) => progress_message(&pool, &pool_progress),
+            None => format!("Reading {}", pool.title()),
+        },
+        Err(Error::NoPoolFound) => "No pool has been found for the tags".to_string(),
+        Err(Error::TooManyTags(problem)) => format!("No pool can be searched for: {}", problem),
+        Err(err) => return Err(err),
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Stops posting pool pages, the channel gets posts from its tags again
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn stop(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let content = if ctx.data().pool_progress(guild, channel).await.is_some() {
+        ctx.data().set_pool_progress(guild, channel, None).await;
+        "Stopped reading the pool, posts are searched by the tags again"
+    } else {
+        "No pool is being read"
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+async fn show_pool(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let content = match ctx.data().pool_progress(guild, channel).await {
+        Some(pool_progress) => {
+            ctx.defer_ephemeral().await?;
+            let pool = ctx
+                .data()
+                .pool(guild, channel, pool_progress.pool_id)
+                .await?;
+            progress_message(&pool, &pool_progress)
+        }
+        None => "No pool is being read".to_string(),
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+fn progress_message(pool: &PoolInfo, pool_progress: &PoolProgress) -> String {
+    let mut content = format!(
+        "Reading {} (#{})\nNext page: {}/{}",
+        pool.title(),
+        pool.id,
+        (pool_progress.page + 1).min(pool.post_ids.len()),
+        pool.post_ids.len()
+    );
+    if let Some(tags) = &pool_progress.random_tags {
+        content.push_str(&format!(
+            "\nRandom pools with the tags: {}",
+            if tags.is_empty() {
+                "none".to_string()
+            } else {
+                tags.join(" ")
+            }
+        ));
+    }
+    content
+}
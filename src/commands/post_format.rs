@@ -0,0 +1,80 @@
+use poise::send_reply;
+
+use crate::{configuration::PostFormat, constants::MAXIMUM_GALLERY_SIZE, Context, Error};
+
+/// Gets or sets if the channel gets one post at a time or several at once
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn post_format(
+    ctx: Context<'_>,
+    #[description = "How posts are sent"] format: Option<PostFormat>,
+    #[description = "How many posts are sent at once by galleries and pages"] size: Option<u64>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    if let Some(size) = size {
+        if !(2..=MAXIMUM_GALLERY_SIZE).contains(&size) {
+            let content = format!(
+                "Gallery size must be between 2 and {}",
+                MAXIMUM_GALLERY_SIZE
+            );
+            send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+            return Ok(());
+        }
+    }
+
+    let current_post_format = ctx.data().post_format(guild, channel).await;
+    let current_gallery_size = ctx.data().gallery_size(guild, channel).await;
+
+    let mut lines = Vec::new();
+
+    if let Some(new_post_format) = format {
+        if let Some(current_post_format) = current_post_format {
+            lines.push(format!(
+                "Old post format: {}\nNew post format: {}",
+                current_post_format, new_post_format
+            ));
+        } else {
+            lines.push(format!(
+                "Old post format is not set.\nNew post format: {}",
+                new_post_format
+            ));
+        }
+
+        ctx.data()
+            .set_post_format(guild, channel, new_post_format)
+            .await;
+    } else if let Some(current_post_format) = current_post_format {
+        lines.push(format!("Post format: {}", current_post_format));
+    } else {
+        lines.push("Post format is not set.".to_string());
+    }
+
+    if let Some(new_gallery_size) = size {
+        if let Some(current_gallery_size) = current_gallery_size {
+            lines.push(format!(
+                "Old gallery size: {} posts\nNew gallery size: {} posts",
+                current_gallery_size, new_gallery_size
+            ));
+        } else {
+            lines.push(format!(
+                "Old gallery size is not set.\nNew gallery size: {} posts",
+                new_gallery_size
+            ));
+        }
+
+        ctx.data()
+            .set_gallery_size(guild, channel, new_gallery_size)
+            .await;
+    } else if let Some(current_gallery_size) = current_gallery_size {
+        lines.push(format!("Gallery size: {} posts", current_gallery_size));
+    }
+
+    send_reply(ctx, |f| f.content(lines.join("\n")).ephemeral(true)).await?;
+
+    Ok(())
+}
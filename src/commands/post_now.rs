@@ -0,0 +1,75 @@
+use poise::{send_reply, serenity_prelude::Timestamp};
+use tracing::{error, info};
+
+use crate::{
+    commands::tags::autocomplete_tags, messenger::Messenger, utils::message_from_post, Context,
+    Error,
+};
+
+/// Immediately posts an image in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn post_now(
+    ctx: Context<'_>,
+    #[description = "If provided, will search for these tags just this once"]
+    #[autocomplete = "autocomplete_tags"]
+    tags: Option<String>,
+    #[description = "If true, restarts the timer of the running loop"] reset_timer: Option<bool>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    ctx.defer_ephemeral().await?;
+
+    let post = if let Some(tags) = tags {
+        let tags: Vec<String> = tags
+            .split_ascii_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+        ctx.data().get_post_with_tags(guild, channel, tags).await
+    } else {
+        ctx.data().get_post(guild, channel).await
+    };
+
+    let style = ctx.data().message_style(guild, channel).await;
+
+    let content = match post.map(|post| (message_from_post(&post, &style), post)) {
+        Err(err) => {
+            error!("{}", err);
+            format!("Could not get a post: {}", err)
+        }
+        Ok((Err(err), post)) => {
+            error!("{}", err);
+            format!("Could not post #{}: {}", post.id, err)
+        }
+        Ok((Ok(message), post)) => {
+            let post_id = post.id;
+            info!(
+                "Posting {} in guild {} in channel {}",
+                post_id, guild, channel
+            );
+            ctx.data().messenger().send_post(channel, message).await?;
+            if let Some(md5) = post.md5 {
+                ctx.data().remember_post(channel, md5).await;
+            }
+
+            ctx.data().update_task_state(channel, |state| {
+                state.last_post_at = Some(Timestamp::now());
+                state.last_post_id = Some(post_id);
+            });
+
+            if reset_timer.unwrap_or_default() && ctx.data().reset_timer(guild, channel).await {
+                format!("Posted #{}. The timer has been reset.", post_id)
+            } else {
+                format!("Posted #{}.", post_id)
+            }
+        }
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
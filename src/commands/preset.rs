@@ -0,0 +1,160 @@
+use futures::{stream, Stream};
+use poise::send_reply;
+
+use crate::{Context, Error};
+
+/// Manages named sets of tags which can be shared between channels
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    subcommands("save", "apply", "list", "delete")
+)]
+pub async fn preset(ctx: Context<'_>) -> Result<(), Error> {
+    list_presets(ctx).await
+}
+
+/// Saves the tags of the channel as a preset and links the channel to it
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn save(
+    ctx: Context<'_>,
+    #[description = "Name of the preset. Overwrites an existing preset with the same name"]
+    name: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+    let name = name.to_lowercase();
+
+    let content = match ctx.data().tags(guild, channel).await {
+        Some(tags) => {
+            let overwritten = ctx.data().presets(guild).await.contains_key(&name);
+            ctx.data().save_preset(guild, name.clone(), tags).await;
+            ctx.data().apply_preset(guild, channel, &name).await;
+            if overwritten {
+                format!(
+                    "Preset `{}` has been updated. All channels linked to it use the new tags.",
+                    name
+                )
+            } else {
+                format!("Preset `{}` has been saved.", name)
+            }
+        }
+        None => "Tags are not set.\n".to_string(),
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Links the channel to a preset, so it uses the tags of the preset
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn apply(
+    ctx: Context<'_>,
+    #[description = "Name of the preset"]
+    #[autocomplete = "autocomplete_preset"]
+    name: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+    let name = name.to_lowercase();
+
+    let content = if ctx.data().apply_preset(guild, channel, &name).await {
+        format!("Channel is now linked to preset `{}`.", name)
+    } else {
+        format!("There is no preset named `{}`.", name)
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Lists the presets of the guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    list_presets(ctx).await
+}
+
+/// Deletes a preset. Channels linked to it keep its tags
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "Name of the preset"]
+    #[autocomplete = "autocomplete_preset"]
+    name: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let name = name.to_lowercase();
+
+    let content = if ctx.data().delete_preset(guild, &name).await {
+        format!("Preset `{}` has been deleted.", name)
+    } else {
+        format!("There is no preset named `{}`.", name)
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Suggests the names of the guild's presets
+async fn autocomplete_preset(ctx: Context<'_>, partial: String) -> impl Stream<Item = String> {
+    let presets = match ctx.guild_id() {
+        Some(guild) => ctx.data().presets(guild).await,
+        None => Default::default(),
+    };
+
+    stream::iter(
+        presets
+            .into_keys()
+            .filter(move |name| name.starts_with(&partial.to_lowercase())),
+    )
+}
+
+/// Replies with the presets of the guild
+async fn list_presets(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let current = ctx.data().preset(guild, ctx.channel_id()).await;
+    let mut presets: Vec<(String, Vec<String>)> =
+        ctx.data().presets(guild).await.into_iter().collect();
+    presets.sort();
+
+    let content = if presets.is_empty() {
+        "There are no presets in this guild.".to_string()
+    } else {
+        presets
+            .into_iter()
+            .map(|(name, tags)| {
+                let linked = if current.as_ref() == Some(&name) {
+                    " (linked to this channel)"
+                } else {
+                    ""
+                };
+                format!("`{}`{}: {}", name, linked, tags.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
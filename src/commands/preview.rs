@@ -0,0 +1,123 @@
+use poise::send_reply;
+use tracing::error;
+
+use crate::{
+    commands::tags::autocomplete_tags,
+    configuration::{NsfwMode, SourceKind},
+    constants::{PREVIEW_SAMPLE_COUNT, PREVIEW_SEARCH_LIMIT},
+    utils::embed_from_post,
+    Context, Error,
+};
+
+/// Searches for tags without changing the channel's configuration
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn preview(
+    ctx: Context<'_>,
+    #[description = "The tags to search for"]
+    #[autocomplete = "autocomplete_tags"]
+    tags: String,
+    #[description = "Nsfw mode, defaults to the channel's nsfw mode"] nsfw: Option<NsfwMode>,
+    #[description = "The site to search, defaults to the channel's source"] source: Option<
+        SourceKind,
+    >,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    ctx.defer_ephemeral().await?;
+
+    let nsfw_mode = match nsfw {
+        Some(nsfw_mode) => nsfw_mode,
+        None => ctx
+            .data()
+            .nsfw_mode(guild, channel)
+            .await
+            .unwrap_or_default(),
+    };
+
+    let source = match source {
+        Some(source) => source,
+        None => ctx.data().source(guild, channel).await.unwrap_or_default(),
+    };
+
+    let layout = ctx
+        .data()
+        .embed_layout(guild, channel)
+        .await
+        .unwrap_or_default();
+
+    let tags: Vec<String> = tags
+        .split_ascii_whitespace()
+        .map(|s| s.to_string())
+        .collect();
+
+    let mut content = String::new();
+
+    // the tag database is e621's, so the tags of other sites can't be checked
+    let unknown_tags = match source {
+        SourceKind::E621 => ctx.data().tag_api().unknown_tags(&tags).await,
+        _ => Ok(Vec::new()),
+    };
+
+    match unknown_tags {
+        Ok(unknown_tags) => {
+            for unknown_tag in unknown_tags {
+                content.push_str(&format!(
+                    "Warning: tag `{}` does not exist.",
+                    unknown_tag.name
+                ));
+                if let Some(suggestion) = unknown_tag.suggestion {
+                    content.push_str(&format!(" Did you mean `{}`?", suggestion));
+                }
+                content.push('\n');
+            }
+        }
+        Err(err) => {
+            error!("Could not look up tags: {}", err);
+            content.push_str("Warning: could not check if the tags exist.\n");
+        }
+    }
+
+    // sites which only return a single page can't count any further
+    let limit = PREVIEW_SEARCH_LIMIT.min(ctx.data().post_source(source).result_limit());
+
+    let posts = match ctx
+        .data()
+        .search_posts(guild, source, nsfw_mode, tags, limit)
+        .await
+    {
+        Ok(posts) => posts,
+        Err(err) => {
+            error!("{}", err);
+            content.push_str(&format!("Search failed: {}", err));
+            send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+            return Ok(());
+        }
+    };
+
+    content.push_str(&match posts.len() {
+        0 => format!("No results on {} ({}).", source, nsfw_mode),
+        count if count >= limit => {
+            format!("At least {} results on {} ({}).", limit, source, nsfw_mode)
+        }
+        count => format!("{} results on {} ({}).", count, source, nsfw_mode),
+    });
+
+    let embeds: Vec<_> = posts
+        .iter()
+        .filter_map(|post| embed_from_post(post, layout).ok())
+        .take(PREVIEW_SAMPLE_COUNT)
+        .collect();
+
+    send_reply(ctx, |f| {
+        f.embeds.extend(embeds);
+        f.content(content).ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
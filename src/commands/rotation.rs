@@ -0,0 +1,260 @@
+use poise::{send_reply, serenity_prelude::CreateEmbed};
+use tracing::error;
+
+use crate::{
+    commands::tags::autocomplete_tags,
+    configuration::{SourceKind, TagSet},
+    filter::check_tag_limit,
+    Context, Error,
+};
+
+/// Manages the tag sets the channel rotates between
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    subcommands("add", "remove", "list", "clear")
+)]
+pub async fn rotation(ctx: Context<'_>) -> Result<(), Error> {
+    list_tag_sets(ctx).await
+}
+
+/// Adds a tag set to the rotation or replaces one with the same name
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "Name of the tag set"] name: String,
+    #[description = "How often the tag set is picked, relative to the other tag sets"] weight: u32,
+    #[description = "The tags to search for"]
+    #[autocomplete = "autocomplete_tags"]
+    tags: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+    let name = name.to_lowercase();
+
+    if weight == 0 {
+        send_reply(ctx, |f| {
+            f.content("Weight must be greater than 0").ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    // validating takes a few requests to e621
+    ctx.defer_ephemeral().await?;
+
+    let tags: Vec<String> = tags
+        .split_ascii_whitespace()
+        .map(|s| s.to_string())
+        .collect();
+
+    let source = ctx.data().source(guild, channel).await.unwrap_or_default();
+    let tag_limit = ctx.data().post_source(source).tag_limit();
+    if let Err(problem) = check_tag_limit(&tags, source, tag_limit) {
+        let content = format!("Tag set has not been added:\n{}", problem);
+        send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let (tags, warnings) = if source != SourceKind::E621 {
+        // the tag database is e621's, so the tags of other sites can't be checked
+        (
+            tags,
+            vec![format!("Tags can not be checked for {}", source)],
+        )
+    } else {
+        match ctx.data().tag_api().validate(&tags).await {
+            Ok(validation) if validation.is_valid() => (validation.tags, validation.warnings),
+            Ok(validation) => {
+                let content = format!(
+                    "Tag set has not been added:\n{}",
+                    validation.errors.join("\n")
+                );
+                send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+                return Ok(());
+            }
+            Err(err) => {
+                error!("Could not validate tags: {}", err);
+                (tags, vec!["Could not check if the tags exist".to_string()])
+            }
+        }
+    };
+
+    let mut tag_sets = ctx
+        .data()
+        .tag_sets(guild, channel)
+        .await
+        .unwrap_or_default();
+    tag_sets.retain(|tag_set| tag_set.name != name);
+    tag_sets.push(TagSet { name, weight, tags });
+    tag_sets.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let embed = rotation_embed(ctx, &tag_sets);
+    ctx.data().set_tag_sets(guild, channel, tag_sets).await;
+
+    let mut content = "Tag set has been added.".to_string();
+    for warning in warnings {
+        content.push_str(&format!("\nWarning: {}", warning));
+    }
+
+    send_reply(ctx, |f| {
+        f.embeds.push(embed);
+        f.content(content).ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Removes a tag set from the rotation
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Name of the tag set"] name: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+    let name = name.to_lowercase();
+
+    let mut tag_sets = ctx
+        .data()
+        .tag_sets(guild, channel)
+        .await
+        .unwrap_or_default();
+    let count = tag_sets.len();
+    tag_sets.retain(|tag_set| tag_set.name != name);
+
+    let content = if tag_sets.len() == count {
+        format!("There is no tag set named `{}`.", name)
+    } else if tag_sets.is_empty() {
+        "Tag set has been removed. The channel uses its tags again.".to_string()
+    } else {
+        "Tag set has been removed.".to_string()
+    };
+
+    let embed = rotation_embed(ctx, &tag_sets);
+    ctx.data().set_tag_sets(guild, channel, tag_sets).await;
+
+    send_reply(ctx, |f| {
+        f.embeds.push(embed);
+        f.content(content).ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Shows the tag sets of the rotation and how often each one has been posted
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    list_tag_sets(ctx).await
+}
+
+/// Removes all tag sets, so the channel uses its tags again
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    ctx.data().set_tag_sets(guild, channel, Vec::new()).await;
+
+    send_reply(ctx, |f| {
+        f.content("All tag sets have been removed. The channel uses its tags again.")
+            .ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Replies with the tag sets of the channel
+async fn list_tag_sets(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let tag_sets = ctx
+        .data()
+        .tag_sets(guild, channel)
+        .await
+        .unwrap_or_default();
+
+    if tag_sets.is_empty() {
+        send_reply(ctx, |f| {
+            f.content("There are no tag sets. The channel uses its tags.")
+                .ephemeral(true)
+        })
+        .await?;
+    } else {
+        let embed = rotation_embed(ctx, &tag_sets);
+        send_reply(ctx, |f| {
+            f.embeds.push(embed);
+            f.ephemeral(true)
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Creates an embed with the expected and the actually posted share of each tag set
+fn rotation_embed(ctx: Context<'_>, tag_sets: &[TagSet]) -> CreateEmbed {
+    let counts = ctx
+        .data()
+        .task_state(ctx.channel_id())
+        .map(|state| state.tag_set_counts)
+        .unwrap_or_default();
+
+    let total_weight: u32 = tag_sets.iter().map(|tag_set| tag_set.weight).sum();
+    let total_count: u64 = tag_sets
+        .iter()
+        .filter_map(|tag_set| counts.get(&tag_set.name))
+        .sum();
+
+    let mut embed = CreateEmbed::default();
+    embed.colour(0x203f6c_u32).title("Rotation");
+
+    for tag_set in tag_sets.iter().take(25) {
+        let count = counts.get(&tag_set.name).copied().unwrap_or_default();
+        let expected = f64::from(tag_set.weight) * 100.0 / f64::from(total_weight.max(1));
+        let actual = if total_count == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / total_count as f64
+        };
+
+        let mut value = format!(
+            "Weight {} ({:.0}%), posted {} times ({:.0}%)\n{}",
+            tag_set.weight,
+            expected,
+            count,
+            actual,
+            tag_set.tags.join(" ")
+        );
+        // discord does not allow longer field values
+        if value.chars().count() > 1024 {
+            value = value.chars().take(1020).collect::<String>() + " ...";
+        }
+
+        embed.field(&tag_set.name, value, false);
+    }
+
+    embed.footer(|footer| footer.text("Post counts are reset when the bot restarts"));
+    embed
+}
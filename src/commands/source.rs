@@ -0,0 +1,80 @@
+use poise::{
+    send_reply,
+    serenity_prelude::{ChannelId, GuildId},
+};
+
+use crate::{configuration::SourceKind, filter::check_tag_limit, Context, Error};
+
+/// Gets or sets the site images are fetched from for the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn source(
+    ctx: Context<'_>,
+    #[description = "The site to fetch images from"] source: Option<SourceKind>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let current_source = ctx.data().source(guild, channel).await;
+
+    let content = if let Some(new_source) = source {
+        if let Err(problem) = check_channel_tags(ctx, guild, channel, new_source).await {
+            let content = format!("Source has not been changed:\n{}", problem);
+            send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+            return Ok(());
+        }
+
+        let mut content = if let Some(current_source) = current_source {
+            format!("Old source: {}\nNew source: {}", current_source, new_source)
+        } else {
+            format!("Old source is not set.\nNew source: {}", new_source)
+        };
+
+        // every site has its own tags
+        if current_source.unwrap_or_default() != new_source {
+            content.push_str(
+                "\nThe tags have not been changed, make sure they exist on the new site.",
+            );
+        }
+
+        ctx.data().set_source(guild, channel, new_source).await;
+
+        content
+    } else if let Some(current_source) = current_source {
+        current_source.to_string()
+    } else {
+        "Source is not set.\n".to_string()
+    };
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Checks if the tags and the tag sets of the channel fit into the searches of a source
+async fn check_channel_tags(
+    ctx: Context<'_>,
+    guild: GuildId,
+    channel: ChannelId,
+    source: SourceKind,
+) -> Result<(), String> {
+    let tag_limit = ctx.data().post_source(source).tag_limit();
+
+    let tags = ctx.data().tags(guild, channel).await.unwrap_or_default();
+    check_tag_limit(&tags, source, tag_limit).map_err(|problem| format!("Tags: {}", problem))?;
+
+    let tag_sets = ctx
+        .data()
+        .tag_sets(guild, channel)
+        .await
+        .unwrap_or_default();
+    for tag_set in tag_sets {
+        check_tag_limit(&tag_set.tags, source, tag_limit)
+            .map_err(|problem| format!("Tag set {}: {}", tag_set.name, problem))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use poise::send_reply;
+
+use crate::{
+    commands::tags::autocomplete_tags,
+    query::{is_metatag, tag_name},
+    Context, Error,
+};
+
+/// Gets or changes the tags whose posts are sent behind a spoiler in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    subcommands("show", "add", "remove")
+)]
+pub async fn spoiler_tags(ctx: Context<'_>) -> Result<(), Error> {
+    show_spoiler_tags(ctx).await
+}
+
+/// Shows the tags whose posts are sent behind a spoiler in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    show_spoiler_tags(ctx).await
+}
+
+/// Adds tags whose posts are sent behind a spoiler in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "The tags to hide posts for. May contain * as a wildcard"]
+    #[autocomplete = "autocomplete_tags"]
+    tags: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let added: Vec<String> = tags
+        .split_ascii_whitespace()
+        .map(|tag| tag_name(tag).to_lowercase())
+        .collect();
+
+    if let Some(metatag) = added.iter().find(|tag| is_metatag(tag)) {
+        let content = format!(
+            "Spoiler tags have not been changed: metatags like `{}` can not be used",
+            metatag
+        );
+        send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let mut spoiler_tags = ctx
+        .data()
+        .spoiler_tags(guild, channel)
+        .await
+        .unwrap_or_default();
+    for tag in added {
+        if !spoiler_tags.contains(&tag) {
+            spoiler_tags.push(tag);
+        }
+    }
+
+    let content = format!("New spoiler tags: {}", format_spoiler_tags(&spoiler_tags));
+
+    ctx.data()
+        .set_spoiler_tags(guild, channel, spoiler_tags)
+        .await;
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Removes tags whose posts are sent behind a spoiler in the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "The tags to no longer hide posts for"] tags: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let removed: Vec<String> = tags
+        .split_ascii_whitespace()
+        .map(|tag| tag_name(tag).to_lowercase())
+        .collect();
+
+    let mut spoiler_tags = ctx
+        .data()
+        .spoiler_tags(guild, channel)
+        .await
+        .unwrap_or_default();
+    spoiler_tags.retain(|tag| !removed.contains(tag));
+
+    let content = format!("New spoiler tags: {}", format_spoiler_tags(&spoiler_tags));
+
+    ctx.data()
+        .set_spoiler_tags(guild, channel, spoiler_tags)
+        .await;
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+/// Replies with the spoiler tags of the channel
+async fn show_spoiler_tags(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let spoiler_tags = ctx
+        .data()
+        .spoiler_tags(guild, channel)
+        .await
+        .unwrap_or_default();
+    let content = format_spoiler_tags(&spoiler_tags);
+
+    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+
+    Ok(())
+}
+
+fn format_spoiler_tags(spoiler_tags: &[String]) -> String {
+    if spoiler_tags.is_empty() {
+        "No spoiler tags are set.".to_string()
+    } else {
+        spoiler_tags
+            .iter()
+            .map(|tag| format!("`{}`", tag))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
@@ -0,0 +1,141 @@
+use poise::{
+    send_reply,
+    serenity_prelude::{ChannelId, CreateEmbed, GuildId, Mention, Timestamp},
+};
+
+use crate::{
+    configuration::PostFormat,
+    constants::EMBED_FIELD_LIMIT,
+    utils::{join_truncated, split_embeds, truncate},
+    Context, Data, Error,
+};
+
+/// Shows the status of the posting loop for the channel or the whole guild
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn status(
+    ctx: Context<'_>,
+    #[description = "Show every channel in the guild"] guild_wide: Option<bool>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+
+    let channels = if guild_wide.unwrap_or_default() {
+        ctx.data().channels(guild).await
+    } else {
+        vec![ctx.channel_id()]
+    };
+
+    let mut embeds = Vec::with_capacity(channels.len());
+    for channel in channels {
+        embeds.push(status_embed(ctx.data(), guild, channel).await);
+    }
+
+    if embeds.is_empty() {
+        let content = "No channels have been configured in this guild.";
+        send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    // discord limits the embeds of a message, the other channels follow in more messages
+    for message in split_embeds(embeds) {
+        send_reply(ctx, |f| {
+            f.embeds.extend(message);
+            f.ephemeral(true)
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Create an embed describing the state of a single channel
+async fn status_embed(data: &Data, guild: GuildId, channel: ChannelId) -> CreateEmbed {
+    let active = data.is_active(guild, channel).await;
+    let state = data.task_state(channel).unwrap_or_default();
+    let config = data.channel_configuration(guild, channel).await;
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .colour(0x203f6c_u32)
+        .description(Mention::from(channel))
+        .field("Active", if active { "yes" } else { "no" }, true)
+        .field("Next post", format_timestamp(state.next_post_at), true)
+        .field("Last post", format_timestamp(state.last_post_at), true);
+
+    if let Some(post_id) = state.last_post_id {
+        embed.field("Last post ID", format!("#{}", post_id), true);
+    }
+
+    embed.field(
+        "Last error",
+        state.last_error.map_or_else(
+            || "none".to_string(),
+            |err| truncate(&err, EMBED_FIELD_LIMIT),
+        ),
+        false,
+    );
+
+    if let Some(config) = config {
+        let tags = data.tags(guild, channel).await.unwrap_or_default();
+        embed
+            .field("Timeout", format!("{} minutes", config.timeout), true)
+            .field("Timeout mode", config.timeout_mode, true)
+            .field("Nsfw mode", config.nsfw_mode, true)
+            .field("Source", config.source, true)
+            .field("Media", config.media_mode, true)
+            .field("Embed layout", config.embed_layout, true)
+            .field("Duplicate check", config.duplicate_check, true)
+            .field(
+                "Post format",
+                match config.post_format {
+                    PostFormat::Single => config.post_format.to_string(),
+                    _ => format!("{} ({} posts)", config.post_format, config.gallery_size),
+                },
+                true,
+            )
+            .field(
+                "Pool",
+                match &config.pool {
+                    Some(pool) => format!("#{} (next page {})", pool.pool_id, pool.page + 1),
+                    None => "none".to_string(),
+                },
+                true,
+            )
+            .field(
+                "Spoiler tags",
+                if config.spoiler_tags.is_empty() {
+                    "none".to_string()
+                } else {
+                    join_truncated(&config.spoiler_tags, EMBED_FIELD_LIMIT)
+                },
+                true,
+            )
+            .field(
+                "Tags",
+                if tags.is_empty() {
+                    "none".to_string()
+                } else {
+                    join_truncated(&tags, EMBED_FIELD_LIMIT)
+                },
+                false,
+            );
+
+        if let Some(preset) = config.preset {
+            embed.field("Preset", preset, true);
+        }
+    } else {
+        embed.field("Configuration", "not set", false);
+    }
+
+    embed
+}
+
+/// Format a timestamp using discord's relative time markdown
+fn format_timestamp(timestamp: Option<Timestamp>) -> String {
+    timestamp
+        .map(|t| format!("<t:{}:R>", t.unix_timestamp()))
+        .unwrap_or_else(|| "never".to_string())
+}
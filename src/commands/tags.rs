@@ -1,48 +1,353 @@
-use poise::send_reply;
+use futures::{stream, Stream};
+use poise::{
+    send_reply,
+    serenity_prelude::{ChannelId, CreateEmbed, GuildId},
+};
+use tracing::error;
 
-use crate::{Context, Error};
+use crate::{
+    configuration::{ChannelConfiguration, SourceKind},
+    constants::EMBED_FIELD_LIMIT,
+    filter::check_tag_limit,
+    query::{tag_kind, tag_name, tag_prefix, TagKind},
+    utils::join_truncated,
+    Context, Error,
+};
 
-/// Gets or sets the tags for the channel
+/// Discord does not allow autocomplete choices longer than this
+const MAXIMUM_CHOICE_LENGTH: usize = 100;
+
+/// Suggests popular tags for the last tag typed so far
+pub async fn autocomplete_tags(ctx: Context<'_>, partial: String) -> impl Stream<Item = String> {
+    let (previous, last) = match partial.rsplit_once(' ') {
+        Some((previous, last)) => (format!("{} ", previous), last),
+        None => (String::new(), partial.as_str()),
+    };
+
+    let suggestions = if tag_kind(last) == TagKind::Meta {
+        Vec::new()
+    } else {
+        match ctx
+            .data()
+            .tag_api()
+            .autocomplete_cached(tag_name(last))
+            .await
+        {
+            Ok(tags) => tags
+                .into_iter()
+                .map(|tag| format!("{}{}{}", previous, tag_prefix(last), tag.name))
+                .filter(|choice| choice.chars().count() <= MAXIMUM_CHOICE_LENGTH)
+                .collect(),
+            Err(err) => {
+                error!("Could not autocomplete tags: {}", err);
+                Vec::new()
+            }
+        }
+    };
+
+    stream::iter(suggestions)
+}
+
+/// Gets or changes the tags for the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS",
+    subcommands("show", "set", "add", "remove", "reset")
+)]
+pub async fn tags(ctx: Context<'_>) -> Result<(), Error> {
+    show_tags(ctx).await
+}
+
+/// Shows the tags for the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    show_tags(ctx).await
+}
+
+/// Replaces all tags for the channel
 #[poise::command(
     prefix_command,
     slash_command,
     required_permissions = "MANAGE_CHANNELS"
 )]
-pub async fn tags(
+pub async fn set(
     ctx: Context<'_>,
-    #[description = "If provided, will set these as the new tags"] tags: Option<String>,
+    #[description = "The new tags"]
+    #[autocomplete = "autocomplete_tags"]
+    tags: String,
+) -> Result<(), Error> {
+    save_tags(ctx, split_tags(&tags)).await
+}
+
+/// Adds tags to the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "The tags to add"]
+    #[autocomplete = "autocomplete_tags"]
+    tags: String,
 ) -> Result<(), Error> {
     let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
     let channel = ctx.channel_id();
 
-    let current_tags = ctx.data().tags(guild, channel).await;
+    let mut new_tags = ctx.data().tags(guild, channel).await.unwrap_or_default();
+    for tag in split_tags(&tags) {
+        if !new_tags.contains(&tag) {
+            new_tags.push(tag);
+        }
+    }
 
-    let content = if let Some(new_tags) = tags {
-        let new_tags: Vec<String> = new_tags
-            .split_ascii_whitespace()
-            .map(|s| s.to_string())
-            .collect();
+    save_tags(ctx, new_tags).await
+}
+
+/// Removes tags from the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "The tags to remove, including their - or ~ prefix"]
+    #[autocomplete = "autocomplete_current_tags"]
+    tags: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let current_tags = ctx.data().tags(guild, channel).await.unwrap_or_default();
+    let removed_tags = split_tags(&tags);
 
-        let content = if let Some(current_tags) = current_tags {
-            format!(
-                "Old tags: {}\nNew tags: {}",
-                current_tags.join(" "),
-                &new_tags.join(" ")
-            )
-        } else {
-            format!("Old tags are not set.\nNew tags: {}", new_tags.join(" "))
-        };
-
-        ctx.data().set_tags(guild, channel, new_tags).await;
-
-        content
-    } else if let Some(current_tags) = current_tags {
-        current_tags.join(" ")
+    let missing_tags: Vec<&String> = removed_tags
+        .iter()
+        .filter(|tag| !current_tags.contains(tag))
+        .collect();
+    if !missing_tags.is_empty() {
+        let content = format!(
+            "Tags have not been changed. These tags are not set: {}",
+            missing_tags
+                .iter()
+                .map(|tag| format!("`{}`", tag))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let new_tags: Vec<String> = current_tags
+        .into_iter()
+        .filter(|tag| !removed_tags.contains(tag))
+        .collect();
+
+    let mut content = "Tags have been removed.".to_string();
+    if let Some(note) = set_channel_tags(ctx, guild, channel, new_tags.clone()).await {
+        content.push_str(&format!("\n{}", note));
+    }
+
+    send_reply(ctx, |f| {
+        f.embeds.push(tags_embed(&new_tags));
+        f.content(content).ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Restores the default tags for the channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    required_permissions = "MANAGE_CHANNELS"
+)]
+pub async fn reset(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    let new_tags = ChannelConfiguration::default().tags;
+    let mut content = "Tags have been reset to the defaults.".to_string();
+    if let Some(note) = set_channel_tags(ctx, guild, channel, new_tags.clone()).await {
+        content.push_str(&format!("\n{}", note));
+    }
+
+    send_reply(ctx, |f| {
+        f.embeds.push(tags_embed(&new_tags));
+        f.content(content).ephemeral(true)
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Suggests tags which are currently set for the channel
+async fn autocomplete_current_tags(
+    ctx: Context<'_>,
+    partial: String,
+) -> impl Stream<Item = String> {
+    let (previous, last) = match partial.rsplit_once(' ') {
+        Some((previous, last)) => (format!("{} ", previous), last.to_string()),
+        None => (String::new(), partial.clone()),
+    };
+
+    let current_tags = match ctx.guild_id() {
+        Some(guild) => ctx
+            .data()
+            .tags(guild, ctx.channel_id())
+            .await
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    stream::iter(
+        current_tags
+            .into_iter()
+            .filter(move |tag| tag.starts_with(&last))
+            .map(move |tag| format!("{}{}", previous, tag))
+            .filter(|choice| choice.chars().count() <= MAXIMUM_CHOICE_LENGTH),
+    )
+}
+
+/// Splits user input into tags
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split_ascii_whitespace()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Sets the tags of the channel, which unlinks it from its preset.
+///
+/// Returns a note for the reply if the channel has been following a preset.
+async fn set_channel_tags(
+    ctx: Context<'_>,
+    guild: GuildId,
+    channel: ChannelId,
+    tags: Vec<String>,
+) -> Option<String> {
+    let preset = ctx.data().preset(guild, channel).await;
+    ctx.data().set_tags(guild, channel, tags).await;
+    preset.map(|preset| {
+        format!(
+            "The channel no longer follows the preset `{}`, use `/preset apply` to follow it again.",
+            preset
+        )
+    })
+}
+
+/// Replies with the current tags of the channel
+async fn show_tags(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    match ctx.data().tags(guild, channel).await {
+        Some(current_tags) => {
+            send_reply(ctx, |f| {
+                f.embeds.push(tags_embed(&current_tags));
+                f.ephemeral(true)
+            })
+            .await?
+        }
+        None => send_reply(ctx, |f| f.content("Tags are not set.\n").ephemeral(true)).await?,
+    };
+
+    Ok(())
+}
+
+/// Validates the tags and saves them if they are valid
+async fn save_tags(ctx: Context<'_>, new_tags: Vec<String>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(Error::CommandNotRunInGuild)?;
+    let channel = ctx.channel_id();
+
+    // validating takes a few requests to e621
+    ctx.defer_ephemeral().await?;
+
+    let source = ctx.data().source(guild, channel).await.unwrap_or_default();
+    let tag_limit = ctx.data().post_source(source).tag_limit();
+    if let Err(problem) = check_tag_limit(&new_tags, source, tag_limit) {
+        let content = format!("Tags have not been changed:\n{}", problem);
+        send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+        return Ok(());
+    }
+
+    let (new_tags, warnings) = if source != SourceKind::E621 {
+        // the tag database is e621's, so the tags of other sites can't be checked
+        (
+            new_tags,
+            vec![format!("Tags can not be checked for {}", source)],
+        )
     } else {
-        "Tags are not set.\n".to_string()
+        match ctx.data().tag_api().validate(&new_tags).await {
+            Ok(validation) if validation.is_valid() => (validation.tags, validation.warnings),
+            Ok(validation) => {
+                let content = format!(
+                    "Tags have not been changed:\n{}",
+                    validation.errors.join("\n")
+                );
+                send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+                return Ok(());
+            }
+            Err(err) => {
+                error!("Could not validate tags: {}", err);
+                (
+                    new_tags,
+                    vec!["Could not check if the tags exist".to_string()],
+                )
+            }
+        }
     };
 
-    send_reply(ctx, |f| f.content(content).ephemeral(true)).await?;
+    let mut content = match ctx.data().tags(guild, channel).await {
+        Some(current_tags) => format!("Old tags: {}", current_tags.join(" ")),
+        None => "Old tags are not set.".to_string(),
+    };
+
+    for warning in warnings {
+        content.push_str(&format!("\nWarning: {}", warning));
+    }
+
+    if let Some(note) = set_channel_tags(ctx, guild, channel, new_tags.clone()).await {
+        content.push_str(&format!("\n{}", note));
+    }
+
+    send_reply(ctx, |f| {
+        f.embeds.push(tags_embed(&new_tags));
+        f.content(content).ephemeral(true)
+    })
+    .await?;
 
     Ok(())
 }
+
+/// Creates an embed listing the tags grouped by how they are used
+fn tags_embed(tags: &[String]) -> CreateEmbed {
+    let group = |kind: TagKind| {
+        let tags: Vec<String> = tags
+            .iter()
+            .filter(|tag| tag_kind(tag) == kind)
+            .map(|tag| format!("`{}`", tag))
+            .collect();
+        if tags.is_empty() {
+            return "none".to_string();
+        }
+        join_truncated(&tags, EMBED_FIELD_LIMIT)
+    };
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .colour(0x203f6c_u32)
+        .title("Tags")
+        .field("Required", group(TagKind::Required), false)
+        .field("Optional", group(TagKind::Optional), false)
+        .field("Excluded", group(TagKind::Excluded), false)
+        .field("Meta", group(TagKind::Meta), false)
+        .footer(|footer| footer.text(format!("{} tags", tags.len())));
+    embed
+}
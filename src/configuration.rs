@@ -7,11 +7,13 @@ use poise::{
     serenity_prelude::{ChannelId, RoleId},
     ChoiceParameter,
 };
+use rand::distributions::{Distribution, WeightedIndex};
 use tokio::sync::watch;
 use tracing::error;
 
 use crate::{
-    constants::{MAXIMUM_TIMEOUT_MINUTES, MINIMUM_TIMEOUT_MINUTES},
+    constants::{DEFAULT_GALLERY_SIZE, MAXIMUM_TIMEOUT_MINUTES, MINIMUM_TIMEOUT_MINUTES},
+    sources::MediaKind,
     Error,
 };
 
@@ -23,11 +25,35 @@ pub struct GuildConfiguration {
     /// roles which are allowed to use the bot
     #[allow(unused)]
     pub(crate) moderator_roles: HashSet<RoleId>,
+    /// tags which are excluded in every channel, without the `-` prefix
+    pub(crate) blacklist: Vec<String>,
+    /// artists whose posts are never sent in the guild
+    pub(crate) blocked_artists: Vec<String>,
+    /// named sets of tags which channels can be linked to
+    pub(crate) presets: HashMap<String, Vec<String>>,
+    /// channel errors of the posting loops are reported in, instead of their own channels
+    pub(crate) log_channel: Option<ChannelId>,
     /// signal for every channel that is running right now
     pub(crate) stop_signals: HashMap<ChannelId, watch::Sender<bool>>,
+    /// signal for every channel that is running right now to restart its timer
+    pub(crate) reset_signals: HashMap<ChannelId, watch::Sender<()>>,
 }
 
 impl GuildConfiguration {
+    /// Copy of the configuration, without the signals of the running tasks
+    pub fn snapshot(&self) -> Self {
+        Self {
+            channels: self.channels.clone(),
+            moderator_roles: self.moderator_roles.clone(),
+            blacklist: self.blacklist.clone(),
+            blocked_artists: self.blocked_artists.clone(),
+            presets: self.presets.clone(),
+            log_channel: self.log_channel,
+            stop_signals: Default::default(),
+            reset_signals: Default::default(),
+        }
+    }
+
     pub fn insert(
         &mut self,
         channel: ChannelId,
@@ -36,8 +62,14 @@ impl GuildConfiguration {
         self.channels.insert(channel, config)
     }
 
-    pub fn start(&mut self, channel: ChannelId, stop_sender: watch::Sender<bool>) {
+    pub fn start(
+        &mut self,
+        channel: ChannelId,
+        stop_sender: watch::Sender<bool>,
+        reset_sender: watch::Sender<()>,
+    ) {
         self.stop_signals.entry(channel).or_insert(stop_sender);
+        self.reset_signals.entry(channel).or_insert(reset_sender);
         self.channels.entry(channel).or_default().active = true;
     }
 
@@ -47,9 +79,26 @@ impl GuildConfiguration {
                 error!("Could not send stop signal for {}: {}", channel, err);
             }
         };
+        self.reset_signals.remove(&channel);
         self.channels.entry(channel).or_default().active = false;
     }
 
+    /// Restarts the timer of a running sending task.
+    ///
+    /// Returns false if there is no task running for the channel.
+    pub fn reset_timer(&self, channel: ChannelId) -> bool {
+        match self.reset_signals.get(&channel) {
+            Some(reset_signal) => {
+                if let Err(err) = reset_signal.send(()) {
+                    error!("Could not send reset signal for {}: {}", channel, err);
+                    return false;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Stops all sending tasks
     pub fn stop_all(&mut self) {
         self.stop_signals.iter().for_each(|(channel, stop_signal)| {
@@ -58,6 +107,7 @@ impl GuildConfiguration {
             }
         });
         self.stop_signals.clear();
+        self.reset_signals.clear();
         self.channels.iter_mut().for_each(|(_, channel_conf)| {
             channel_conf.active = false;
         });
@@ -87,12 +137,161 @@ impl GuildConfiguration {
         self.channels.entry(channel).or_default().nsfw_mode = nsfw_mode;
     }
 
+    pub fn source(&self, channel: &ChannelId) -> Option<SourceKind> {
+        self.channels.get(channel).map(|c| c.source)
+    }
+
+    pub fn set_source(&mut self, channel: ChannelId, source: SourceKind) {
+        self.channels.entry(channel).or_default().source = source;
+    }
+
+    pub fn media_mode(&self, channel: &ChannelId) -> Option<MediaMode> {
+        self.channels.get(channel).map(|c| c.media_mode)
+    }
+
+    pub fn set_media_mode(&mut self, channel: ChannelId, media_mode: MediaMode) {
+        self.channels.entry(channel).or_default().media_mode = media_mode;
+    }
+
+    pub fn embed_layout(&self, channel: &ChannelId) -> Option<EmbedLayout> {
+        self.channels.get(channel).map(|c| c.embed_layout)
+    }
+
+    pub fn set_embed_layout(&mut self, channel: ChannelId, embed_layout: EmbedLayout) {
+        self.channels.entry(channel).or_default().embed_layout = embed_layout;
+    }
+
+    pub fn duplicate_check(&self, channel: &ChannelId) -> Option<DuplicateCheck> {
+        self.channels.get(channel).map(|c| c.duplicate_check)
+    }
+
+    pub fn set_duplicate_check(&mut self, channel: ChannelId, duplicate_check: DuplicateCheck) {
+        self.channels.entry(channel).or_default().duplicate_check = duplicate_check;
+    }
+
+    pub fn post_format(&self, channel: &ChannelId) -> Option<PostFormat> {
+        self.channels.get(channel).map(|c| c.post_format)
+    }
+
+    pub fn set_post_format(&mut self, channel: ChannelId, post_format: PostFormat) {
+        self.channels.entry(channel).or_default().post_format = post_format;
+    }
+
+    pub fn pool(&self, channel: &ChannelId) -> Option<PoolProgress> {
+        self.channels.get(channel).and_then(|c| c.pool.clone())
+    }
+
+    pub fn set_pool(&mut self, channel: ChannelId, pool: Option<PoolProgress>) {
+        self.channels.entry(channel).or_default().pool = pool;
+    }
+
+    pub fn gallery_size(&self, channel: &ChannelId) -> Option<u64> {
+        self.channels.get(channel).map(|c| c.gallery_size)
+    }
+
+    pub fn set_gallery_size(&mut self, channel: ChannelId, gallery_size: u64) {
+        self.channels.entry(channel).or_default().gallery_size = gallery_size;
+    }
+
+    /// The tags of a channel, or the tags of its preset if it is linked to one
     pub fn tags(&self, channel: &ChannelId) -> Option<&Vec<String>> {
-        self.channels.get(channel).map(|c| &c.tags)
+        let config = self.channels.get(channel)?;
+        config
+            .preset
+            .as_ref()
+            .and_then(|preset| self.presets.get(preset))
+            .or(Some(&config.tags))
     }
 
+    /// Sets the tags of a channel, unlinking it from its preset
     pub fn set_tags(&mut self, channel: ChannelId, tags: Vec<String>) {
-        self.channels.entry(channel).or_default().tags = tags;
+        let config = self.channels.entry(channel).or_default();
+        config.tags = tags;
+        config.preset = None;
+    }
+
+    pub fn tag_sets(&self, channel: &ChannelId) -> Option<&Vec<TagSet>> {
+        self.channels.get(channel).map(|c| &c.tag_sets)
+    }
+
+    pub fn set_tag_sets(&mut self, channel: ChannelId, tag_sets: Vec<TagSet>) {
+        self.channels.entry(channel).or_default().tag_sets = tag_sets;
+    }
+
+    pub fn spoiler_tags(&self, channel: &ChannelId) -> Option<&Vec<String>> {
+        self.channels.get(channel).map(|c| &c.spoiler_tags)
+    }
+
+    pub fn set_spoiler_tags(&mut self, channel: ChannelId, spoiler_tags: Vec<String>) {
+        self.channels.entry(channel).or_default().spoiler_tags = spoiler_tags;
+    }
+
+    pub fn preset(&self, channel: &ChannelId) -> Option<&String> {
+        self.channels.get(channel).and_then(|c| c.preset.as_ref())
+    }
+
+    pub fn presets(&self) -> &HashMap<String, Vec<String>> {
+        &self.presets
+    }
+
+    /// Creates or overwrites a preset. Linked channels use the new tags right away.
+    pub fn save_preset(&mut self, name: String, tags: Vec<String>) {
+        self.presets.insert(name, tags);
+    }
+
+    /// Links a channel to a preset. Returns false if there is no such preset
+    pub fn apply_preset(&mut self, channel: ChannelId, name: &str) -> bool {
+        let tags = match self.presets.get(name) {
+            Some(tags) => tags.clone(),
+            None => return false,
+        };
+        let config = self.channels.entry(channel).or_default();
+        // keep a copy, so the channel still has tags if the preset gets deleted
+        config.tags = tags;
+        config.preset = Some(name.to_string());
+        true
+    }
+
+    /// Deletes a preset. Channels linked to it keep its tags.
+    ///
+    /// Returns false if there is no such preset
+    pub fn delete_preset(&mut self, name: &str) -> bool {
+        let tags = match self.presets.remove(name) {
+            Some(tags) => tags,
+            None => return false,
+        };
+        self.channels
+            .values_mut()
+            .filter(|c| c.preset.as_deref() == Some(name))
+            .for_each(|c| {
+                c.tags = tags.clone();
+                c.preset = None;
+            });
+        true
+    }
+
+    pub fn blacklist(&self) -> &Vec<String> {
+        &self.blacklist
+    }
+
+    pub fn set_blacklist(&mut self, blacklist: Vec<String>) {
+        self.blacklist = blacklist;
+    }
+
+    pub fn blocked_artists(&self) -> &Vec<String> {
+        &self.blocked_artists
+    }
+
+    pub fn set_blocked_artists(&mut self, blocked_artists: Vec<String>) {
+        self.blocked_artists = blocked_artists;
+    }
+
+    pub fn log_channel(&self) -> Option<ChannelId> {
+        self.log_channel
+    }
+
+    pub fn set_log_channel(&mut self, log_channel: Option<ChannelId>) {
+        self.log_channel = log_channel;
     }
 
     pub fn is_active(&self, channel: ChannelId) -> bool {
@@ -114,8 +313,57 @@ pub struct ChannelConfiguration {
     pub(crate) timeout_mode: TimeoutMode,
     /// If the query should return sfw or nsfw posts
     pub(crate) nsfw_mode: NsfwMode,
+    /// The site posts are fetched from
+    pub(crate) source: SourceKind,
+    /// The kinds of files which are posted
+    pub(crate) media_mode: MediaMode,
+    /// How much information about a post is shown
+    pub(crate) embed_layout: EmbedLayout,
+    /// Where posts are looked for, to skip files which have been posted before
+    pub(crate) duplicate_check: DuplicateCheck,
+    /// If posts are sent one at a time or several at once
+    pub(crate) post_format: PostFormat,
+    /// How many posts are sent at once by the gallery and pages formats
+    pub(crate) gallery_size: u64,
     /// The tags to search for
     pub(crate) tags: Vec<String>,
+    /// Name of the preset the tags are taken from instead
+    pub(crate) preset: Option<String>,
+    /// Tag sets to rotate between. If not empty, they are used instead of the tags
+    pub(crate) tag_sets: Vec<TagSet>,
+    /// Posts with any of these tags are sent behind a spoiler. May contain `*` wildcards
+    pub(crate) spoiler_tags: Vec<String>,
+    /// The pool which is read page by page instead of searching for posts
+    pub(crate) pool: Option<PoolProgress>,
+}
+
+/// How far a channel has read an e621 pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolProgress {
+    pub(crate) pool_id: u64,
+    /// index of the next page to post
+    pub(crate) page: usize,
+    /// if set, another random pool with these tags is read once this one is finished
+    pub(crate) random_tags: Option<Vec<String>>,
+}
+
+/// A named set of tags which is picked with a probability
+/// proportional to its weight
+#[derive(Debug, Clone)]
+pub struct TagSet {
+    pub(crate) name: String,
+    pub(crate) weight: u32,
+    pub(crate) tags: Vec<String>,
+}
+
+impl ChannelConfiguration {
+    /// Randomly picks one of the tag sets by their weight
+    pub fn pick_tag_set(&self) -> Option<&TagSet> {
+        let distribution =
+            WeightedIndex::new(self.tag_sets.iter().map(|tag_set| tag_set.weight)).ok()?;
+        let mut rng = rand::thread_rng();
+        self.tag_sets.get(distribution.sample(&mut rng))
+    }
 }
 
 impl Default for ChannelConfiguration {
@@ -125,6 +373,12 @@ impl Default for ChannelConfiguration {
             timeout: 40,
             timeout_mode: TimeoutMode::Normal,
             nsfw_mode: NsfwMode::SFW,
+            source: SourceKind::E621,
+            media_mode: MediaMode::All,
+            embed_layout: EmbedLayout::Standard,
+            duplicate_check: DuplicateCheck::Channel,
+            post_format: PostFormat::Single,
+            gallery_size: DEFAULT_GALLERY_SIZE,
             tags: vec![
                 "pokémon_(species)",
                 "-abs",
@@ -160,6 +414,10 @@ impl Default for ChannelConfiguration {
             .into_iter()
             .map(|s| s.to_string())
             .collect(),
+            preset: None,
+            tag_sets: Vec::new(),
+            spoiler_tags: Vec::new(),
+            pool: None,
         }
     }
 }
@@ -189,6 +447,171 @@ impl Display for NsfwMode {
     }
 }
 
+/// The site posts are fetched from. Default is e621
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ChoiceParameter)]
+pub enum SourceKind {
+    #[name = "e621"]
+    E621,
+    #[name = "danbooru"]
+    Danbooru,
+    #[name = "gelbooru"]
+    Gelbooru,
+    #[name = "safebooru"]
+    Safebooru,
+    #[name = "local"]
+    Local,
+}
+
+impl Default for SourceKind {
+    fn default() -> Self {
+        Self::E621
+    }
+}
+
+impl Display for SourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::E621 => write!(f, "e621"),
+            Self::Danbooru => write!(f, "danbooru"),
+            Self::Gelbooru => write!(f, "gelbooru"),
+            Self::Safebooru => write!(f, "safebooru"),
+            Self::Local => write!(f, "local"),
+        }
+    }
+}
+
+/// The kinds of files a channel posts. Flash is never posted. Default is all
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum MediaMode {
+    #[name = "all"]
+    All,
+    #[name = "no_videos"]
+    NoVideos,
+    #[name = "still_images"]
+    StillImages,
+    #[name = "animated_only"]
+    AnimatedOnly,
+}
+
+impl MediaMode {
+    pub fn allows(&self, kind: MediaKind) -> bool {
+        match (self, kind) {
+            (_, MediaKind::Flash) => false,
+            (Self::All, _) => true,
+            (Self::NoVideos, kind) => kind != MediaKind::Video,
+            (Self::StillImages, kind) => kind == MediaKind::Image,
+            (Self::AnimatedOnly, kind) => kind != MediaKind::Image,
+        }
+    }
+}
+
+impl Default for MediaMode {
+    fn default() -> Self {
+        Self::All
+    }
+}
+
+impl Display for MediaMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::NoVideos => write!(f, "no_videos"),
+            Self::StillImages => write!(f, "still_images"),
+            Self::AnimatedOnly => write!(f, "animated_only"),
+        }
+    }
+}
+
+/// How much information about a post is shown in its embed. Default is standard
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ChoiceParameter)]
+pub enum EmbedLayout {
+    /// The image, the artists and a link to the post
+    #[name = "minimal"]
+    Minimal,
+    /// Also the description, rating, characters, species, score and upload date
+    #[name = "standard"]
+    Standard,
+    /// Also the full description, copyrights and sources
+    #[name = "detailed"]
+    Detailed,
+}
+
+impl Default for EmbedLayout {
+    fn default() -> Self {
+        Self::Standard
+    }
+}
+
+impl Display for EmbedLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Minimal => write!(f, "minimal"),
+            Self::Standard => write!(f, "standard"),
+            Self::Detailed => write!(f, "detailed"),
+        }
+    }
+}
+
+/// Where a channel looks for posts with the same file, to skip them. Default is channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum DuplicateCheck {
+    /// Files are posted again whenever they are found
+    #[name = "off"]
+    Off,
+    /// Files which have been posted in the channel are skipped
+    #[name = "channel"]
+    Channel,
+    /// Files which have been posted in any channel of the guild are skipped
+    #[name = "guild"]
+    Guild,
+}
+
+impl Default for DuplicateCheck {
+    fn default() -> Self {
+        Self::Channel
+    }
+}
+
+impl Display for DuplicateCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Channel => write!(f, "channel"),
+            Self::Guild => write!(f, "guild"),
+        }
+    }
+}
+
+/// How many posts a channel gets each time. Default is single
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum PostFormat {
+    /// One post per message
+    #[name = "single"]
+    Single,
+    /// Several posts in one message, one embed each
+    #[name = "gallery"]
+    Gallery,
+    /// Several posts in one message, flipped through with buttons
+    #[name = "pages"]
+    Pages,
+}
+
+impl Default for PostFormat {
+    fn default() -> Self {
+        Self::Single
+    }
+}
+
+impl Display for PostFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Single => write!(f, "single"),
+            Self::Gallery => write!(f, "gallery"),
+            Self::Pages => write!(f, "pages"),
+        }
+    }
+}
+
 /// Timeout mode. Default is normal
 #[derive(Debug, Clone, Copy, ChoiceParameter)]
 pub enum TimeoutMode {
@@ -2,7 +2,54 @@
 pub static MINIMUM_TIMEOUT_MINUTES: u64 = 3;
 /// in minutes
 pub static MAXIMUM_TIMEOUT_MINUTES: u64 = 60 * 12;
+/// placeholder for the operator in the user agent while `USER_AGENT_CONTACT` isn't set.
+/// It must not name anyone, or forks would be mistaken for them
+pub static DEFAULT_USER_AGENT_CONTACT: &str = "contact not set";
+/// used unless `E621_URL` is set
+pub static DEFAULT_E621_URL: &str = "https://e621.net";
+/// used unless `E926_URL` is set
+pub static DEFAULT_E926_URL: &str = "https://e926.net";
 /// prefix for all redis requests
 pub static REDIS_PREFIX: &str = "e6bot";
 /// separator for redis keys
 pub static REDIS_PATH_SEPARATOR: &str = "::";
+/// maximum amount of posts fetched by /preview to count results
+pub static PREVIEW_SEARCH_LIMIT: usize = 320;
+/// amount of sample posts shown by /preview
+pub static PREVIEW_SAMPLE_COUNT: usize = 3;
+/// maximum amount of tags e621 allows per search for regular accounts
+pub static MAXIMUM_SEARCH_TAGS: usize = 40;
+/// amount of tags added to every e621 search by the bot itself
+pub static RESERVED_SEARCH_TAGS: usize = 2;
+/// in minutes
+pub static AUTOCOMPLETE_CACHE_MINUTES: u64 = 60;
+/// maximum amount of prefixes kept in the autocomplete cache
+pub static AUTOCOMPLETE_CACHE_SIZE: usize = 1000;
+/// maximum amount of posts looked at when searching for a post to send
+pub static POST_SEARCH_LIMIT: usize = 320;
+/// tags e621 gives artists who don't want their works posted elsewhere
+pub static DO_NOT_POST_TAGS: [&str; 2] = ["conditional_dnp", "avoid_posting"];
+/// hashes of posted files remembered per channel, to skip duplicates
+pub static POST_HISTORY_SIZE: usize = 1000;
+/// searches for a post which has not been posted before, before giving up
+pub static DUPLICATE_SEARCH_ATTEMPTS: usize = 5;
+/// votes needed to delete a post
+pub static DELETE_VOTES_NEEDED: usize = 4;
+/// posts which can't be shown that are skipped in a row before a channel is stopped
+pub static BAD_POST_ATTEMPTS: usize = 5;
+/// most characters discord allows in the description of an embed
+pub static EMBED_DESCRIPTION_LIMIT: usize = 4096;
+/// most characters discord allows in a field of an embed
+pub static EMBED_FIELD_LIMIT: usize = 1024;
+/// most characters discord allows in an embed, and in all embeds of a message together
+pub static EMBEDS_TOTAL_LIMIT: usize = 6000;
+/// most characters of the description shown by the standard embed layout
+pub static SHORT_DESCRIPTION_LIMIT: usize = 300;
+/// most bytes discord allows the files of one message to have together, in guilds without boosts
+pub static MAXIMUM_UPLOAD_SIZE: u64 = 10 * 1024 * 1024;
+/// most embeds discord allows in one message, so most posts sent at once
+pub static MAXIMUM_GALLERY_SIZE: u64 = 10;
+/// posts sent at once by the gallery and pages formats, unless set otherwise
+pub static DEFAULT_GALLERY_SIZE: u64 = 4;
+/// messages with pages which can still be flipped through
+pub static GALLERY_CACHE_SIZE: usize = 100;
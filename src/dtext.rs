@@ -0,0 +1,616 @@
+//! Converts e621's DText markup into discord markdown
+
+use reqwest::Url;
+
+use crate::utils::escape_markdown;
+
+/// Internal references like `post #123`, and the pages they link to
+static REFERENCES: [(&str, &str); 12] = [
+    ("post", "posts"),
+    ("pool", "pools"),
+    ("set", "post_sets"),
+    ("comment", "comments"),
+    ("forum", "forum_posts"),
+    ("topic", "forum_topics"),
+    ("user", "users"),
+    ("wiki", "wiki_pages"),
+    ("artist", "artists"),
+    ("note", "notes"),
+    ("ticket", "tickets"),
+    ("blip", "blips"),
+];
+
+/// Inline formats and their markdown markers
+static FORMATS: [(&str, &str); 5] = [
+    ("b", "**"),
+    ("i", "*"),
+    ("u", "__"),
+    ("s", "~~"),
+    ("spoiler", "||"),
+];
+
+/// A part of the converted text
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    /// Plain text, escaped when rendered. Can be cut between words
+    Text(String),
+    /// Markdown which can't be cut, like links
+    Atom(String),
+    /// Starts a format, like `**`
+    Open(&'static str),
+    /// Ends a format
+    Close(&'static str),
+    Newline,
+}
+
+/// Converts DText to discord markdown of at most `limit` characters.
+///
+/// References like `post #123` and relative links are resolved against `base_url`,
+/// like `https://e621.net`. Without it, they are left as text.
+pub fn to_markdown(dtext: &str, base_url: Option<&str>, limit: usize) -> String {
+    let pieces = Parser::new(base_url).parse(&dtext.replace("\r\n", "\n"));
+    render(&pieces, limit)
+}
+
+struct Parser<'a> {
+    base_url: Option<&'a str>,
+    pieces: Vec<Piece>,
+    text: String,
+    /// how many `[quote]`s are open
+    quote_depth: usize,
+    /// true while inside of a `h1.` to `h6.` line
+    header: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(base_url: Option<&'a str>) -> Self {
+        Self {
+            base_url: base_url.map(|url| url.trim_end_matches('/')),
+            pieces: Vec::new(),
+            text: String::new(),
+            quote_depth: 0,
+            header: false,
+        }
+    }
+
+    fn parse(mut self, dtext: &str) -> Vec<Piece> {
+        let mut rest = dtext;
+        let mut line_start = true;
+
+        while let Some(c) = rest.chars().next() {
+            if line_start {
+                if let Some(after) = self.header_start(rest) {
+                    rest = after;
+                    line_start = false;
+                    continue;
+                }
+            }
+
+            let parsed = match c {
+                '\n' => {
+                    self.newline();
+                    Some(&rest[1..])
+                }
+                '[' => self
+                    .wiki_link(rest)
+                    .or_else(|| self.code_block(rest))
+                    .or_else(|| self.tag(rest, line_start)),
+                '{' => self.tag_search(rest),
+                '"' => self.named_link(rest),
+                '<' => self.angle_link(rest),
+                'h' if rest.starts_with("http://") || rest.starts_with("https://") => {
+                    self.bare_link(rest)
+                }
+                _ if self.at_word_start() => self.reference(rest),
+                _ => None,
+            };
+
+            line_start = c == '\n';
+            rest = match parsed {
+                Some(after) => after,
+                None => {
+                    self.text.push(c);
+                    &rest[c.len_utf8()..]
+                }
+            };
+        }
+
+        if self.header {
+            self.push(Piece::Close("**"));
+        }
+        self.flush();
+        self.pieces
+    }
+
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            self.pieces
+                .push(Piece::Text(std::mem::take(&mut self.text)));
+        }
+    }
+
+    fn push(&mut self, piece: Piece) {
+        self.flush();
+        self.pieces.push(piece);
+    }
+
+    fn newline(&mut self) {
+        if self.header {
+            self.push(Piece::Close("**"));
+            self.header = false;
+        }
+        self.push(Piece::Newline);
+        if self.quote_depth > 0 {
+            self.push(Piece::Atom("> ".to_string()));
+        }
+    }
+
+    fn at_word_start(&self) -> bool {
+        !self
+            .text
+            .chars()
+            .last()
+            .map(|c| c.is_alphanumeric())
+            .unwrap_or_default()
+    }
+
+    /// `h1.` to `h6.` at the start of a line, shown in bold
+    fn header_start<'t>(&mut self, rest: &'t str) -> Option<&'t str> {
+        let mut chars = rest.chars();
+        let is_header =
+            chars.next()? == 'h' && ('1'..='6').contains(&chars.next()?) && chars.next()? == '.';
+        if !is_header {
+            return None;
+        }
+        self.push(Piece::Open("**"));
+        self.header = true;
+        Some(rest[3..].trim_start_matches(' '))
+    }
+
+    /// `[b]`, `[/b]`, `[quote]`, `[section=Title]` and so on
+    fn tag<'t>(&mut self, rest: &'t str, line_start: bool) -> Option<&'t str> {
+        let end = rest.find(']')?;
+        let tag = &rest[1..end];
+        if tag.len() > 32 || tag.contains('\n') {
+            return None;
+        }
+        let after = &rest[end + 1..];
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let (name, value) = match tag.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (tag, None),
+        };
+        // `[section,expanded=Title]` is shown like any other section
+        let name = name.split(',').next().unwrap_or_default().to_lowercase();
+
+        if let Some((_, marker)) = FORMATS.iter().find(|(format, _)| *format == name) {
+            self.push(if closing {
+                Piece::Close(marker)
+            } else {
+                Piece::Open(marker)
+            });
+            return Some(after);
+        }
+
+        match (name.as_str(), closing) {
+            ("quote", false) => {
+                if !line_start {
+                    self.push(Piece::Newline);
+                }
+                self.quote_depth += 1;
+                self.push(Piece::Atom("> ".to_string()));
+                Some(after.strip_prefix('\n').unwrap_or(after))
+            }
+            ("quote", true) => {
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                self.push(Piece::Newline);
+                Some(after.strip_prefix('\n').unwrap_or(after))
+            }
+            ("section", false) => {
+                if let Some(title) = value.filter(|title| !title.trim().is_empty()) {
+                    self.push(Piece::Open("**"));
+                    self.text.push_str(title.trim());
+                    self.push(Piece::Close("**"));
+                    self.newline();
+                }
+                Some(after.strip_prefix('\n').unwrap_or(after))
+            }
+            ("section", true) => Some(after.strip_prefix('\n').unwrap_or(after)),
+            // only change how the text looks, discord can't show them
+            ("color" | "sup" | "sub" | "tn", _) => Some(after),
+            _ => None,
+        }
+    }
+
+    /// `[code]` keeps everything inside as it is
+    fn code_block<'t>(&mut self, rest: &'t str) -> Option<&'t str> {
+        let inner = rest.strip_prefix("[code]")?;
+        let (code, after) = inner.split_once("[/code]")?;
+        let code = code.trim_matches('\n').replace("```", "`\u{200b}``");
+        self.push(Piece::Atom(format!("```\n{}\n```", code)));
+        Some(after)
+    }
+
+    /// `[[wiki page]]` or `[[wiki page|shown text]]`
+    fn wiki_link<'t>(&mut self, rest: &'t str) -> Option<&'t str> {
+        let inner = rest.strip_prefix("[[")?;
+        let (link, after) = inner.split_once("]]")?;
+        if link.is_empty() || link.contains('\n') {
+            return None;
+        }
+        let (page, name) = link.split_once('|').unwrap_or((link, link));
+        let title = page.trim().to_lowercase().replace(' ', "_");
+        let url = self.resolve_with_query("/wiki_pages/show_or_new", "title", &title);
+        self.link(name.trim(), url);
+        Some(after)
+    }
+
+    /// `{{tag search}}`
+    fn tag_search<'t>(&mut self, rest: &'t str) -> Option<&'t str> {
+        let inner = rest.strip_prefix("{{")?;
+        let (tags, after) = inner.split_once("}}")?;
+        if tags.trim().is_empty() || tags.contains('\n') {
+            return None;
+        }
+        let url = self.resolve_with_query("/posts", "tags", tags.trim());
+        self.link(tags.trim(), url);
+        Some(after)
+    }
+
+    /// `"shown text":url` or `"shown text":[url]`
+    fn named_link<'t>(&mut self, rest: &'t str) -> Option<&'t str> {
+        let inner = &rest[1..];
+        let name_end = inner.find('"')?;
+        let name = &inner[..name_end];
+        if name.is_empty() || name.contains('\n') {
+            return None;
+        }
+        let target = inner[name_end + 1..].strip_prefix(':')?;
+
+        let (url, after) = match target.strip_prefix('[') {
+            Some(bracketed) => {
+                let end = bracketed.find(']')?;
+                (&bracketed[..end], &bracketed[end + 1..])
+            }
+            None => split_url(target),
+        };
+        if url.is_empty() {
+            return None;
+        }
+        let url = self.resolve(url);
+        self.link(name, url);
+        Some(after)
+    }
+
+    /// `<https://example.com>`, which discord shows without a preview as well
+    fn angle_link<'t>(&mut self, rest: &'t str) -> Option<&'t str> {
+        let end = rest.find('>')?;
+        let url = &rest[1..end];
+        if !(url.starts_with("http://") || url.starts_with("https://")) || url.contains(' ') {
+            return None;
+        }
+        self.push(Piece::Atom(format!("<{}>", url)));
+        Some(&rest[end + 1..])
+    }
+
+    fn bare_link<'t>(&mut self, rest: &'t str) -> Option<&'t str> {
+        let (url, after) = split_url(rest);
+        self.push(Piece::Atom(url.to_string()));
+        Some(after)
+    }
+
+    /// `post #123`, `pool #45` and so on
+    fn reference<'t>(&mut self, rest: &'t str) -> Option<&'t str> {
+        let base_url = self.base_url?;
+        let (name, path) = REFERENCES.iter().find(|(name, _)| {
+            rest.get(..name.len())
+                .map(|prefix| prefix.eq_ignore_ascii_case(name))
+                .unwrap_or_default()
+        })?;
+        let number = rest[name.len()..].strip_prefix(" #")?;
+        let digits = number
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(number.len());
+        if digits == 0 {
+            return None;
+        }
+        let id = &number[..digits];
+        let shown = &rest[..name.len() + 2 + digits];
+        let url = format!("{}/{}/{}", base_url, path, id);
+        self.link(shown, Some(url));
+        Some(&number[digits..])
+    }
+
+    /// A masked link, or just its text if the url can't be resolved
+    fn link(&mut self, name: &str, url: Option<String>) {
+        match url {
+            Some(url) => self.push(Piece::Atom(format!(
+                "[{}]({})",
+                escape_markdown(name),
+                url.replace(')', "%29")
+            ))),
+            None => self.text.push_str(name),
+        }
+    }
+
+    /// Makes relative urls absolute
+    fn resolve(&self, url: &str) -> Option<String> {
+        if url.starts_with("http://") || url.starts_with("https://") {
+            Some(url.to_string())
+        } else if url.starts_with('/') {
+            self.base_url.map(|base_url| format!("{}{}", base_url, url))
+        } else {
+            None
+        }
+    }
+
+    fn resolve_with_query(&self, path: &str, key: &str, value: &str) -> Option<String> {
+        let base_url = self.base_url?;
+        Url::parse_with_params(&format!("{}{}", base_url, path), &[(key, value)])
+            .ok()
+            .map(|url| url.to_string())
+    }
+}
+
+/// Splits a url off the start of the text. Punctuation at its end is left in the text
+fn split_url(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| c.is_whitespace() || c == '<' || c == '"')
+        .unwrap_or(text.len());
+    let url = text[..end].trim_end_matches(|c: char| ".,;:!?)".contains(c));
+    (url, &text[url.len()..])
+}
+
+fn length(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Renders the pieces, cutting them at the last word or format which fits into `limit`.
+///
+/// Formats which are still open get closed, so the cut never breaks the markdown.
+fn render(pieces: &[Piece], limit: usize) -> String {
+    let mut out = String::new();
+    let mut open: Vec<&'static str> = Vec::new();
+    for piece in pieces {
+        push_piece(&mut out, &mut open, piece);
+    }
+    close_all(&mut out, &mut open);
+    if length(out.trim()) <= limit {
+        return out.trim().to_string();
+    }
+
+    out.clear();
+    // length of `out` before each format opened right before the cut, which would end up empty
+    let mut trailing_opens: Vec<usize> = Vec::new();
+    for piece in pieces {
+        let extra = match piece {
+            Piece::Close(_) => 0,
+            // room for closing it as well
+            Piece::Open(marker) => 2 * length(marker),
+            Piece::Atom(_) | Piece::Newline => piece_length(piece),
+            Piece::Text(text) => {
+                let escaped = escape_markdown(text);
+                if fits(&out, &open, length(&escaped), limit) {
+                    out.push_str(&escaped);
+                    trailing_opens.clear();
+                    continue;
+                }
+                // as many whole words as fit
+                let mut taken = String::new();
+                for word in text.split_inclusive(char::is_whitespace) {
+                    let word = escape_markdown(word);
+                    if !fits(&out, &open, length(&taken) + length(&word), limit) {
+                        break;
+                    }
+                    taken.push_str(&word);
+                }
+                if !taken.trim().is_empty() {
+                    trailing_opens.clear();
+                }
+                out.push_str(&taken);
+                break;
+            }
+        };
+        if !fits(&out, &open, extra, limit) {
+            break;
+        }
+        let before = out.len();
+        push_piece(&mut out, &mut open, piece);
+        match piece {
+            Piece::Open(_) => trailing_opens.push(before),
+            _ => trailing_opens.clear(),
+        }
+    }
+
+    // drops the empty formats together with any whitespace taken after them
+    if let Some(&start) = trailing_opens.first() {
+        open.truncate(open.len() - trailing_opens.len());
+        out.truncate(start);
+    }
+    out.truncate(out.trim_end().len());
+    out.push('…');
+    close_all(&mut out, &mut open);
+    out.trim().to_string()
+}
+
+/// True if `extra` characters, closing the open formats and the ellipsis fit into `limit`
+fn fits(out: &str, open: &[&str], extra: usize, limit: usize) -> bool {
+    let closing: usize = open.iter().map(|marker| length(marker)).sum();
+    length(out) + extra + closing + 1 <= limit
+}
+
+fn close_all(out: &mut String, open: &mut Vec<&'static str>) {
+    while let Some(marker) = open.pop() {
+        out.push_str(marker);
+    }
+}
+
+fn piece_length(piece: &Piece) -> usize {
+    match piece {
+        Piece::Text(text) => length(&escape_markdown(text)),
+        Piece::Atom(atom) => length(atom),
+        Piece::Open(marker) | Piece::Close(marker) => length(marker),
+        Piece::Newline => 1,
+    }
+}
+
+fn push_piece(out: &mut String, open: &mut Vec<&'static str>, piece: &Piece) {
+    match piece {
+        Piece::Text(text) => out.push_str(&escape_markdown(text)),
+        Piece::Atom(atom) => out.push_str(atom),
+        Piece::Newline => out.push('\n'),
+        Piece::Open(marker) => {
+            out.push_str(marker);
+            open.push(marker);
+        }
+        // closes everything opened after it as well. Unopened formats are left out
+        Piece::Close(marker) => {
+            if let Some(position) = open.iter().rposition(|open| open == marker) {
+                while open.len() > position {
+                    out.push_str(open.pop().unwrap_or_default());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const E621: Option<&str> = Some("https://e621.net");
+
+    fn convert(dtext: &str) -> String {
+        to_markdown(dtext, E621, 4096)
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            convert("[b]bold[/b] and [i]italic[/i]"),
+            "**bold** and *italic*"
+        );
+        assert_eq!(convert("[s]no[/s] [u]yes[/u]"), "~~no~~ __yes__");
+        assert_eq!(convert("[spoiler]it was him[/spoiler]"), "||it was him||");
+        assert_eq!(convert("[color=red]red[/color]"), "red");
+    }
+
+    #[test]
+    fn unclosed_formats_are_closed() {
+        assert_eq!(convert("[b]bold [i]both"), "**bold *both***");
+        assert_eq!(convert("[b]bold [i]both[/b] none"), "**bold *both*** none");
+    }
+
+    #[test]
+    fn markdown_in_text_is_escaped() {
+        assert_eq!(convert("*not bold* a_b"), "\\*not bold\\* a\\_b");
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            convert("\"my gallery\":https://example.com/art."),
+            "[my gallery](https://example.com/art)."
+        );
+        assert_eq!(
+            convert("\"help\":[/wiki_pages/help]"),
+            "[help](https://e621.net/wiki_pages/help)"
+        );
+        assert_eq!(
+            convert("see https://example.com, or <https://example.org>"),
+            "see https://example.com, or <https://example.org>"
+        );
+    }
+
+    #[test]
+    fn references() {
+        assert_eq!(
+            convert("sequel to post #123."),
+            "sequel to [post #123](https://e621.net/posts/123)."
+        );
+        assert_eq!(convert("Pool #7"), "[Pool #7](https://e621.net/pools/7)");
+        assert_eq!(convert("repost #12"), "repost #12");
+        assert_eq!(to_markdown("post #123", None, 4096), "post #123");
+    }
+
+    #[test]
+    fn wiki_links_and_searches() {
+        assert_eq!(
+            convert("[[Tag Group|tag groups]]"),
+            "[tag groups](https://e621.net/wiki_pages/show_or_new?title=tag_group)"
+        );
+        assert_eq!(
+            convert("{{pikachu solo}}"),
+            "[pikachu solo](https://e621.net/posts?tags=pikachu+solo)"
+        );
+    }
+
+    #[test]
+    fn quotes_and_headers() {
+        assert_eq!(
+            convert("[quote]first\nsecond[/quote]\nreply"),
+            "> first\n> second\nreply"
+        );
+        assert_eq!(convert("h2.Title\ntext"), "**Title**\ntext");
+        assert_eq!(
+            convert("[section=Details]hidden[/section]"),
+            "**Details**\nhidden"
+        );
+    }
+
+    #[test]
+    fn code_is_kept() {
+        assert_eq!(convert("[code][b]x[/b][/code]"), "```\n[b]x[/b]\n```");
+    }
+
+    #[test]
+    fn truncates_between_words() {
+        assert_eq!(to_markdown("one two three four", None, 12), "one two…");
+    }
+
+    #[test]
+    fn truncation_closes_formats() {
+        let markdown = to_markdown("[b]one two three four[/b]", None, 14);
+        assert_eq!(markdown, "**one two…**");
+        assert!(length(&markdown) <= 14);
+    }
+
+    #[test]
+    fn truncation_keeps_links_whole() {
+        let markdown = to_markdown("see post #123456 for more", E621, 20);
+        assert_eq!(markdown, "see…");
+    }
+
+    #[test]
+    fn truncation_drops_formats_with_only_whitespace() {
+        assert_eq!(to_markdown("[b] aaaaaaaaaaaaaaaaaaaa[/b]", None, 8), "…");
+        assert_eq!(
+            to_markdown("one [b] aaaaaaaaaaaaaaaaaaaa[/b]", None, 12),
+            "one…"
+        );
+    }
+
+    #[test]
+    fn truncation_drops_formats_with_only_multi_byte_whitespace() {
+        // an ideographic space, as in japanese descriptions
+        assert_eq!(
+            to_markdown("[b]\u{3000}aaaaaaaaaaaaaaaaaaaa[/b]", None, 8),
+            "…"
+        );
+        assert_eq!(
+            to_markdown("[b][i]\u{3000}aaaaaaaaaaaaaaaaaaaa[/i][/b]", None, 10),
+            "…"
+        );
+    }
+
+    #[test]
+    fn truncation_respects_the_limit() {
+        let dtext = "[b]bold[/b] [spoiler]secret[/spoiler] post #1 ".repeat(200);
+        for limit in [10, 50, 100, 4096] {
+            assert!(length(&to_markdown(&dtext, E621, limit)) <= limit);
+        }
+    }
+}
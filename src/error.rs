@@ -12,10 +12,26 @@ pub enum Error {
     Serenity(#[from] poise::serenity_prelude::Error),
     #[error("redis error")]
     Redis(#[from] fred::error::RedisError),
+    #[error("http error")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("json error")]
+    Json(#[from] serde_json::Error),
+    #[error("io error")]
+    Io(#[from] std::io::Error),
     #[error("Command must be run in guild")]
     CommandNotRunInGuild,
     #[error("No tags have been set")]
     NoTagsSet,
+    #[error("No local image directory has been configured")]
+    NoLocalDirectory,
+    #[error("No pool has been found for the tags")]
+    NoPoolFound,
+    #[error("The pool has no more pages")]
+    PoolFinished,
+    #[error("{0}")]
+    TooManyTags(String),
+    #[error("{0} is too large to upload")]
+    FileTooLarge(String),
     #[error("uhhh")]
     Uhhh(String),
     #[error("Min timeout is too low")]
@@ -24,6 +40,47 @@ pub enum Error {
     MaxTimeoutTooHigh,
 }
 
+impl Error {
+    /// Classifies the error, to decide if the loop can keep going after it
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Rs621(rs621::error::Error::Http { code, .. }) => ErrorKind::from_status(*code),
+            Error::Rs621(_) => ErrorKind::Transient,
+            Error::Reqwest(err) => err
+                .status()
+                .map(|status| ErrorKind::from_status(status.as_u16()))
+                .unwrap_or(ErrorKind::Transient),
+            Error::Json(_) | Error::Io(_) | Error::Redis(_) => ErrorKind::Transient,
+            Error::NoTagsSet | Error::NoLocalDirectory | Error::NoPoolFound | Error::TooManyTags(_) => ErrorKind::BadQuery,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+/// What kind of problem an error is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Likely to go away by itself, like rate limits or the site being down
+    Transient,
+    /// The login has been rejected
+    Auth,
+    /// The search itself can't work, like invalid tags or missing configuration
+    BadQuery,
+    /// Anything else, like nothing being found
+    Other,
+}
+
+impl ErrorKind {
+    /// Classifies http status codes of the sites
+    pub fn from_status(code: u16) -> Self {
+        match code {
+            401 | 403 => Self::Auth,
+            400 | 404 | 410 | 422 => Self::BadQuery,
+            408 | 429 | 500..=599 => Self::Transient,
+            _ => Self::Other,
+        }
+    }
+}
 
 pub enum ArgumentError {
     
@@ -0,0 +1,374 @@
+//! Filtering of posts after they have been fetched, for tags
+//! which did not fit into the search query
+
+use crate::{
+    configuration::{NsfwMode, SourceKind},
+    constants::DO_NOT_POST_TAGS,
+    query::{tag_kind, tag_name, TagKind},
+    sources::{Post, Rating},
+};
+
+/// Removes posts which have any of the excluded tags or ratings
+#[derive(Debug, Clone, Default)]
+pub struct PostFilter {
+    /// tag names, without the `-` prefix. May contain `*` wildcards
+    excluded: Vec<String>,
+    /// ratings as used in the `rating:` metatag, for example `s` or `explicit`
+    excluded_ratings: Vec<String>,
+}
+
+impl PostFilter {
+    /// Adds an excluded tag from a query, like `-tag` or `-rating:e`
+    pub fn exclude(&mut self, tag: &str) {
+        let name = tag_name(tag).to_lowercase();
+        match name.strip_prefix("rating:") {
+            Some(rating) => self.excluded_ratings.push(rating.to_string()),
+            None => self.excluded.push(name),
+        }
+    }
+
+    /// Check if a post has none of the excluded tags or ratings
+    pub fn allows(&self, post: &Post) -> bool {
+        let rating = post.rating.name();
+        !self
+            .excluded_ratings
+            .iter()
+            .any(|excluded| rating.starts_with(excluded.as_str()))
+            && !self.excluded.iter().any(|tag| post_has_tag(post, tag))
+    }
+}
+
+/// Check if an excluded tag can be applied by [PostFilter] instead of e621
+pub fn can_filter(tag: &str) -> bool {
+    match tag_kind(tag) {
+        TagKind::Excluded => true,
+        TagKind::Meta => {
+            tag.starts_with('-') && tag_name(tag).to_lowercase().starts_with("rating:")
+        }
+        _ => false,
+    }
+}
+
+/// Merges the tags excluded in every search of `source`, without duplicates.
+///
+/// e621's do not post tags come first, as they exclude the most artists with a
+/// single tag, then the blocked artists and then the blacklist. The other sites
+/// don't have these tags, so they are only added for e621.
+pub fn merge_excluded_tags(
+    source: SourceKind,
+    blocked_artists: impl IntoIterator<Item = String>,
+    blacklist: impl IntoIterator<Item = String>,
+) -> Vec<String> {
+    let mut excluded: Vec<String> = match source {
+        SourceKind::E621 => DO_NOT_POST_TAGS.iter().map(|tag| tag.to_string()).collect(),
+        _ => Vec::new(),
+    };
+    for tag in blocked_artists.into_iter().chain(blacklist) {
+        if !excluded.contains(&tag) {
+            excluded.push(tag);
+        }
+    }
+    excluded
+}
+
+/// Splits a query into the tags sent to e621 and a filter for the tags that don't fit.
+///
+/// Tags which can't be filtered locally always go into the query. Excluded tags
+/// fill it up to `limit`, first the ones of the query and then the blacklist.
+/// The whole blacklist is always part of the filter, so no query can override it.
+pub fn split_query(
+    tags: Vec<String>,
+    blacklist: &[String],
+    limit: usize,
+) -> (Vec<String>, PostFilter) {
+    let (filterable, mut query): (Vec<String>, Vec<String>) =
+        tags.into_iter().partition(|tag| can_filter(tag));
+
+    let mut filter = PostFilter::default();
+    let excluded = filterable
+        .into_iter()
+        .chain(blacklist.iter().map(|tag| format!("-{}", tag)));
+
+    for tag in excluded {
+        if query.len() < limit {
+            query.push(tag.clone());
+        }
+        filter.exclude(&tag);
+    }
+
+    (query, filter)
+}
+
+/// Checks if the tags which can't be filtered after searching fit into a query of `source`,
+/// returning a description of the problem.
+///
+/// Only excluded tags are moved to the [PostFilter], all the others are always searched for.
+pub fn check_tag_limit(tags: &[String], source: SourceKind, limit: usize) -> Result<(), String> {
+    let count = tags.iter().filter(|tag| !can_filter(tag)).count();
+    if count > limit {
+        return Err(format!(
+            "{} only allows {} tags which aren't excluded in a search, but there are {}",
+            source, limit, count
+        ));
+    }
+    Ok(())
+}
+
+/// Check if a post has a tag in any of the tag categories
+pub fn post_has_tag(post: &Post, tag: &str) -> bool {
+    post.tags.iter().any(|t| wildcard_matches(tag, t))
+}
+
+/// The tags out of `tags` a post has, for example to name them in a warning
+pub fn matching_tags<'a>(post: &Post, tags: &'a [String]) -> Vec<&'a str> {
+    tags.iter()
+        .filter(|tag| post_has_tag(post, tag))
+        .map(|tag| tag.as_str())
+        .collect()
+}
+
+/// Check if a post matches a whole query, for sources which can't be searched by a server.
+///
+/// Only `rating:` is supported out of the metatags, the others are ignored.
+pub fn matches_query(post: &Post, tags: &[String]) -> bool {
+    let mut optional = tags
+        .iter()
+        .filter(|tag| tag_kind(tag) == TagKind::Optional)
+        .peekable();
+    if optional.peek().is_some()
+        && !optional.any(|tag| post_has_tag(post, &tag_name(tag).to_lowercase()))
+    {
+        return false;
+    }
+
+    tags.iter().all(|tag| {
+        let name = tag_name(tag).to_lowercase();
+        match tag_kind(tag) {
+            TagKind::Required => post_has_tag(post, &name),
+            TagKind::Excluded => !post_has_tag(post, &name),
+            TagKind::Optional => true,
+            TagKind::Meta => match name.strip_prefix("rating:") {
+                Some(rating) => post.rating.name().starts_with(rating) != tag.starts_with('-'),
+                None => true,
+            },
+        }
+    })
+}
+
+/// Check if a post may be sent in the nsfw mode.
+///
+/// Not every site has a safe counterpart, so this is checked for all of them.
+pub fn allows_rating(nsfw_mode: NsfwMode, post: &Post) -> bool {
+    match nsfw_mode {
+        NsfwMode::SFW => post.rating == Rating::Safe,
+        NsfwMode::NSFW => true,
+    }
+}
+
+/// Matches a tag against a pattern which may contain `*` wildcards
+fn wildcard_matches(pattern: &str, tag: &str) -> bool {
+    if !pattern.contains('*') {
+        return pattern == tag;
+    }
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match tag.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    for (idx, part) in parts.iter().enumerate() {
+        if idx == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::PostTags;
+
+    fn post(rating: Rating, general: &[&str]) -> Post {
+        Post {
+            rating,
+            tags: PostTags {
+                general: general.iter().map(|tag| tag.to_string()).collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_tags_without_wildcards_exactly() {
+        assert!(wildcard_matches("blood", "blood"));
+        assert!(!wildcard_matches("blood", "blood_on_face"));
+        assert!(!wildcard_matches("blood", "bloo"));
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_matches("blood*", "blood_on_face"));
+        assert!(wildcard_matches("blood*", "blood"));
+        assert!(wildcard_matches("*_on_face", "blood_on_face"));
+        assert!(wildcard_matches("*on*", "blood_on_face"));
+        assert!(wildcard_matches("b*d*e", "blood_on_face"));
+        assert!(wildcard_matches("*", "anything"));
+
+        assert!(!wildcard_matches("blood*", "first_blood"));
+        assert!(!wildcard_matches("*_on_face", "blood_on_hands"));
+        assert!(!wildcard_matches("b*d*x", "blood_on_face"));
+    }
+
+    #[test]
+    fn wildcards_do_not_match_overlapping_parts() {
+        // prefix and suffix can't share characters of the tag
+        assert!(!wildcard_matches("ab*ba", "aba"));
+        assert!(wildcard_matches("ab*ba", "abba"));
+    }
+
+    #[test]
+    fn filter_rejects_excluded_tags() {
+        let mut filter = PostFilter::default();
+        filter.exclude("-gore");
+        filter.exclude("-blood*");
+
+        assert!(filter.allows(&post(Rating::Safe, &["pikachu"])));
+        assert!(!filter.allows(&post(Rating::Safe, &["pikachu", "gore"])));
+        assert!(!filter.allows(&post(Rating::Safe, &["blood_on_face"])));
+    }
+
+    #[test]
+    fn filter_rejects_excluded_ratings() {
+        let mut filter = PostFilter::default();
+        filter.exclude("-rating:e");
+        filter.exclude("-Rating:Questionable");
+
+        assert!(filter.allows(&post(Rating::Safe, &[])));
+        assert!(!filter.allows(&post(Rating::Questionable, &[])));
+        assert!(!filter.allows(&post(Rating::Explicit, &[])));
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn e621_excludes_its_do_not_post_tags_first() {
+        let excluded = merge_excluded_tags(
+            SourceKind::E621,
+            tags(&["some_artist"]),
+            tags(&["gore", "some_artist"]),
+        );
+
+        assert_eq!(
+            excluded,
+            tags(&["conditional_dnp", "avoid_posting", "some_artist", "gore"])
+        );
+    }
+
+    #[test]
+    fn other_sites_only_exclude_blocked_artists_and_the_blacklist() {
+        let excluded = merge_excluded_tags(
+            SourceKind::Danbooru,
+            tags(&["some_artist", "other_artist"]),
+            tags(&["other_artist", "gore"]),
+        );
+
+        assert_eq!(excluded, tags(&["some_artist", "other_artist", "gore"]));
+    }
+
+    #[test]
+    fn split_query_keeps_excluded_tags_within_the_limit() {
+        let (query, filter) =
+            split_query(tags(&["pikachu", "-gore", "-blood"]), &tags(&["scat"]), 4);
+
+        assert_eq!(query, tags(&["pikachu", "-gore", "-blood", "-scat"]));
+        assert!(!filter.allows(&post(Rating::Safe, &["scat"])));
+    }
+
+    #[test]
+    fn split_query_filters_excluded_tags_over_the_limit() {
+        let (query, filter) =
+            split_query(tags(&["pikachu", "-gore", "-blood"]), &tags(&["scat"]), 2);
+
+        // tags of the query go first, the blacklist only gets the remaining slots
+        assert_eq!(query, tags(&["pikachu", "-gore"]));
+        assert!(filter.allows(&post(Rating::Safe, &["pikachu"])));
+        assert!(!filter.allows(&post(Rating::Safe, &["pikachu", "gore"])));
+        assert!(!filter.allows(&post(Rating::Safe, &["pikachu", "blood"])));
+        assert!(!filter.allows(&post(Rating::Safe, &["pikachu", "scat"])));
+    }
+
+    #[test]
+    fn split_query_never_drops_tags_it_can_not_filter() {
+        let (query, _) = split_query(tags(&["pikachu", "~eevee", "~vulpix", "score:>10"]), &[], 2);
+
+        assert_eq!(query, tags(&["pikachu", "~eevee", "~vulpix", "score:>10"]));
+    }
+
+    #[test]
+    fn split_query_filters_excluded_ratings() {
+        let (query, filter) = split_query(tags(&["pikachu", "rating:s", "-rating:e"]), &[], 2);
+
+        assert_eq!(query, tags(&["pikachu", "rating:s"]));
+        assert!(filter.allows(&post(Rating::Safe, &[])));
+        assert!(filter.allows(&post(Rating::Questionable, &[])));
+        assert!(!filter.allows(&post(Rating::Explicit, &[])));
+    }
+
+    #[test]
+    fn excluded_tags_do_not_count_towards_the_tag_limit() {
+        let tags = tags(&["pikachu", "~eevee", "-gore", "-rating:e", "-blood"]);
+
+        assert!(check_tag_limit(&tags, SourceKind::Danbooru, 2).is_ok());
+        assert!(check_tag_limit(&tags, SourceKind::Danbooru, 1).is_err());
+    }
+
+    #[test]
+    fn query_matches_required_and_excluded_tags() {
+        let post = post(Rating::Safe, &["pikachu", "solo"]);
+
+        assert!(matches_query(&post, &tags(&["pikachu"])));
+        assert!(matches_query(&post, &tags(&["Pikachu", "-gore"])));
+        assert!(matches_query(&post, &tags(&["pika*"])));
+        assert!(!matches_query(&post, &tags(&["pikachu", "eevee"])));
+        assert!(!matches_query(&post, &tags(&["pikachu", "-solo"])));
+    }
+
+    #[test]
+    fn query_needs_one_of_the_optional_tags() {
+        let post = post(Rating::Safe, &["pikachu"]);
+
+        assert!(matches_query(&post, &tags(&["~eevee", "~pikachu"])));
+        assert!(!matches_query(&post, &tags(&["~eevee", "~vulpix"])));
+    }
+
+    #[test]
+    fn query_matches_ratings() {
+        let post = post(Rating::Questionable, &["pikachu"]);
+
+        assert!(matches_query(&post, &tags(&["rating:q"])));
+        assert!(matches_query(&post, &tags(&["-rating:e"])));
+        assert!(!matches_query(&post, &tags(&["rating:safe"])));
+        assert!(!matches_query(&post, &tags(&["-rating:questionable"])));
+        // other metatags can't be checked and are ignored
+        assert!(matches_query(&post, &tags(&["score:>100"])));
+    }
+
+    #[test]
+    fn empty_filter_allows_everything() {
+        let filter = PostFilter::default();
+
+        assert!(filter.allows(&post(Rating::Explicit, &["gore"])));
+    }
+}
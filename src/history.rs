@@ -0,0 +1,173 @@
+//! Hashes of the files which have been posted, to skip posting them again
+
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::{
+    constants::{MAXIMUM_UPLOAD_SIZE, POST_HISTORY_SIZE},
+    http::download,
+    sources::Post,
+    Error,
+};
+
+/// md5 hashes of the latest files posted in a channel
+#[derive(Debug, Clone, Default)]
+pub struct PostHistory {
+    /// the latest first
+    hashes: VecDeque<String>,
+}
+
+impl PostHistory {
+    pub fn new(hashes: Vec<String>) -> Self {
+        let mut hashes = VecDeque::from(hashes);
+        hashes.truncate(POST_HISTORY_SIZE);
+        Self { hashes }
+    }
+
+    /// Remembers a posted file. The oldest ones are forgotten once there are too many
+    pub fn remember(&mut self, md5: String) {
+        self.hashes.retain(|hash| hash != &md5);
+        self.hashes.push_front(md5);
+        self.hashes.truncate(POST_HISTORY_SIZE);
+    }
+
+    pub fn hashes(&self) -> impl Iterator<Item = &String> {
+        self.hashes.iter()
+    }
+}
+
+/// Hashes the file of a post, for sites which don't tell its md5.
+///
+/// Files on disk are read, everything else is downloaded. Downloaded files are kept
+/// on the post, so they don't have to be downloaded again to upload them. Files too
+/// large to be uploaded aren't hashed, so they can't be checked for duplicates either.
+pub async fn hash_file(client: &reqwest::Client, post: &mut Post) -> Result<(), Error> {
+    let md5 = match (&post.file_path, &post.file_url) {
+        (Some(path), _) => {
+            if tokio::fs::metadata(path).await?.len() > MAXIMUM_UPLOAD_SIZE {
+                return Err(Error::FileTooLarge(path.display().to_string()));
+            }
+            md5::compute(tokio::fs::read(path).await?)
+        }
+        (None, Some(url)) => {
+            let data: Arc<[u8]> = download(client, url, MAXIMUM_UPLOAD_SIZE)
+                .await?
+                .ok_or_else(|| Error::FileTooLarge(url.clone()))?
+                .into();
+            let md5 = md5::compute(&data);
+            post.file_data = Some(data);
+            md5
+        }
+        (None, None) => return Err(Error::Uhhh("No file to hash on post object".to_string())),
+    };
+    post.md5 = Some(format!("{:x}", md5));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+    use crate::http::HttpSettings;
+
+    #[test]
+    fn remembers_latest_first() {
+        let mut history = PostHistory::default();
+        history.remember("a".to_string());
+        history.remember("b".to_string());
+        history.remember("a".to_string());
+
+        let hashes: Vec<&String> = history.hashes().collect();
+        assert_eq!(hashes, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn forgets_oldest_files() {
+        let mut history = PostHistory::default();
+        for i in 0..=POST_HISTORY_SIZE {
+            history.remember(i.to_string());
+        }
+
+        assert_eq!(history.hashes().count(), POST_HISTORY_SIZE);
+        assert!(!history.hashes().any(|hash| hash == "0"));
+    }
+
+    /// md5 of `hello`
+    const HELLO_MD5: &str = "5d41402abc4b2a76b9719d911017c592";
+
+    fn client() -> reqwest::Client {
+        HttpSettings::new("tests", None).client().unwrap()
+    }
+
+    #[tokio::test]
+    async fn hashes_downloaded_files() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/data/1.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes("hello"))
+            .mount(&server)
+            .await;
+
+        let mut post = Post::example(1);
+        post.file_url = Some(format!("{}/data/1.png", server.uri()));
+        hash_file(&client(), &mut post).await.unwrap();
+
+        assert_eq!(post.md5.as_deref(), Some(HELLO_MD5));
+        // kept, so the file is not downloaded again to upload it
+        assert_eq!(post.file_data.as_deref(), Some(&b"hello"[..]));
+    }
+
+    #[tokio::test]
+    async fn hashes_files_on_disk() {
+        let path = std::env::temp_dir().join("cutepokebot-hashes-files-on-disk.png");
+        std::fs::write(&path, "hello").unwrap();
+
+        let mut post = Post::example(1);
+        post.file_url = None;
+        post.file_path = Some(path.clone());
+        let result = hash_file(&client(), &mut post).await;
+        std::fs::remove_file(&path).unwrap();
+
+        result.unwrap();
+        assert_eq!(post.md5.as_deref(), Some(HELLO_MD5));
+        assert!(post.file_data.is_none());
+    }
+
+    #[tokio::test]
+    async fn download_errors_are_returned() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/data/1.png"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let mut post = Post::example(1);
+        post.file_url = Some(format!("{}/data/1.png", server.uri()));
+
+        assert!(hash_file(&client(), &mut post).await.is_err());
+        assert!(post.md5.is_none());
+    }
+
+    #[tokio::test]
+    async fn files_too_large_to_upload_are_not_hashed() {
+        let server = MockServer::start().await;
+        let file = vec![0; MAXIMUM_UPLOAD_SIZE as usize + 1];
+        Mock::given(method("GET"))
+            .and(path("/data/1.webm"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(file))
+            .mount(&server)
+            .await;
+
+        let mut post = Post::example(1);
+        post.file_url = Some(format!("{}/data/1.webm", server.uri()));
+        let result = hash_file(&client(), &mut post).await;
+
+        assert!(matches!(result, Err(Error::FileTooLarge(_))));
+        assert!(post.md5.is_none());
+        assert!(post.file_data.is_none());
+    }
+}
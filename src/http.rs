@@ -0,0 +1,76 @@
+//! Settings shared by the http clients of every site
+
+use tracing::warn;
+
+use crate::{constants::DEFAULT_USER_AGENT_CONTACT, Error};
+
+/// User agent and proxy used for every request to the sites
+#[derive(Debug, Clone)]
+pub struct HttpSettings {
+    /// e621 requires it to name the bot and whoever is running it
+    pub user_agent: String,
+    /// url of a proxy all requests are sent through
+    pub proxy: Option<String>,
+}
+
+impl HttpSettings {
+    /// The user agent always contains the version of the bot, only the contact can be changed
+    pub fn new(contact: &str, proxy: Option<String>) -> Self {
+        Self {
+            user_agent: format!("CutePokebot/{} ({})", env!("CARGO_PKG_VERSION"), contact),
+            proxy,
+        }
+    }
+
+    /// Reads `USER_AGENT_CONTACT` and `PROXY_URL`.
+    ///
+    /// The proxy is only given to the clients built by [HttpSettings::client].
+    pub fn from_env() -> Self {
+        let contact = dotenv::var("USER_AGENT_CONTACT").unwrap_or_else(|_| {
+            warn!(
+                "USER_AGENT_CONTACT is not set, e621 may block requests which don't say who runs the bot"
+            );
+            DEFAULT_USER_AGENT_CONTACT.to_string()
+        });
+        let proxy = dotenv::var("PROXY_URL").ok();
+
+        // rs621 builds its own http client, which only picks up a proxy from the environment
+        if proxy.is_some() && std::env::var_os("HTTPS_PROXY").is_none() {
+            warn!(
+                "PROXY_URL is set but HTTPS_PROXY is not, e621 searches don't go through the proxy"
+            );
+        }
+
+        Self::new(&contact, proxy)
+    }
+
+    /// Builds a client sending the user agent, through the proxy if there is one
+    pub fn client(&self) -> Result<reqwest::Client, Error> {
+        let mut builder = reqwest::Client::builder().user_agent(&self.user_agent);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// Downloads a file, unless it has more than `limit` bytes
+pub async fn download(
+    client: &reqwest::Client,
+    url: &str,
+    limit: u64,
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    if response.content_length().unwrap_or_default() > limit {
+        return Ok(None);
+    }
+    // not every site tells the size up front
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if (data.len() + chunk.len()) as u64 > limit {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(Some(data))
+}
@@ -0,0 +1,24 @@
+//! Everything but the startup of the bot, so it can be used by the integration tests
+
+pub mod checks;
+pub mod commands;
+pub mod configuration;
+pub mod constants;
+pub mod dtext;
+pub mod error;
+pub mod filter;
+pub mod history;
+pub mod http;
+pub mod messenger;
+pub mod persistence;
+pub mod pool_api;
+pub mod query;
+pub mod setup;
+pub mod sources;
+pub mod tag_api;
+pub mod tasks;
+pub mod utils;
+
+pub type Data = setup::Data;
+pub type Error = error::Error;
+pub type Context<'a> = poise::Context<'a, Data, Error>;
@@ -1,23 +1,10 @@
 use std::sync::Arc;
 
+use cutepokebot::{commands, setup, Data, Error};
 use poise::{serenity_prelude::GatewayIntents, Framework};
 use tokio::sync::watch::{self, Receiver};
 use tracing::{debug, error, info, instrument, warn};
 
-mod checks;
-mod commands;
-mod configuration;
-mod constants;
-mod error;
-mod persistence;
-mod setup;
-mod tasks;
-mod utils;
-
-type Data = setup::Data;
-type Error = error::Error;
-type Context<'a> = poise::Context<'a, Data, Error>;
-
 #[tokio::main]
 #[instrument]
 async fn main() {
@@ -43,8 +30,24 @@ async fn main() {
             commands: vec![
                 commands::start::start(),
                 commands::stop::stop(),
+                commands::status::status(),
+                commands::post_now::post_now(),
+                commands::preview::preview(),
                 commands::tags::tags(),
+                commands::blacklist::blacklist(),
+                commands::artist_blocklist::artist_blocklist(),
+                commands::artist_blocklist::global_artist_blocklist(),
+                commands::log_channel::log_channel(),
+                commands::preset::preset(),
+                commands::rotation::rotation(),
                 commands::nsfw::nsfw(),
+                commands::source::source(),
+                commands::media::media(),
+                commands::embed::embed(),
+                commands::duplicates::duplicates(),
+                commands::post_format::post_format(),
+                commands::pool::pool(),
+                commands::spoiler_tags::spoiler_tags(),
                 commands::timeout::timeout(),
                 commands::timeout_mode::timeout_mode(),
                 commands::register::register_in_guild(),
@@ -110,6 +113,18 @@ async fn stop_bot_handler(
         };
     }
 
+    // stored before stopping the tasks, so the channels which are sending
+    // images right now are still marked as active
+    warn!("Storing state to db");
+    {
+        let user_data = framework.user_data().await;
+        debug!("Got user data, storing data");
+        if let Err(err) = user_data.store_to_db().await {
+            error!("Error storing data: {err:?}");
+        } else {
+            debug!("Data stored");
+        }
+    }
     warn!("Shutting down tasks");
     {
         let user_data = framework.user_data().await;
@@ -121,14 +136,4 @@ async fn stop_bot_handler(
     {
         framework.shard_manager().lock().await.shutdown_all().await;
     }
-    warn!("Storing state to db");
-    {
-        let user_data = framework.user_data().await;
-        debug!("Got user data, storing data");
-        if let Err(err) = user_data.store_to_db().await {
-            error!("Error storing data: {err:?}");
-        } else {
-            debug!("Data stored");
-        }
-    }
 }
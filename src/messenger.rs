@@ -0,0 +1,531 @@
+//! Everything the background tasks send to discord, behind a trait so they can run without it
+
+use std::{borrow::Cow, sync::Arc};
+
+use async_trait::async_trait;
+use poise::serenity_prelude::{
+    ActionRowComponent, AttachmentType, ChannelId, CreateActionRow, Http, InteractionResponseType,
+    Message, MessageComponentInteraction, MessageId,
+};
+
+use crate::{
+    constants::{DELETE_VOTES_NEEDED, MAXIMUM_UPLOAD_SIZE},
+    http::download,
+    utils::{
+        page_buttons, post_buttons, read_ahead_button, Attachment, AttachmentSource, Oversized,
+        PostMessage, ReadAhead,
+    },
+    Error,
+};
+
+/// Sends, edits and deletes messages and responds to button clicks
+#[async_trait]
+pub trait Messenger: Clone + Send + Sync + 'static {
+    /// A button click which can be responded to
+    type Interaction: Send + Sync;
+
+    /// Sends the message of a post with the delete button attached
+    async fn send_post(&self, channel: ChannelId, message: PostMessage)
+        -> Result<MessageId, Error>;
+
+    /// Sends the messages of several posts as a single message with an embed for each
+    async fn send_gallery(
+        &self,
+        channel: ChannelId,
+        messages: Vec<PostMessage>,
+    ) -> Result<MessageId, Error>;
+
+    /// Sends the first page of several posts, with buttons to flip through them
+    async fn send_pages(&self, channel: ChannelId, page: PostMessage) -> Result<MessageId, Error>;
+
+    /// Sends a plain text message
+    async fn say(&self, channel: ChannelId, content: &str) -> Result<(), Error>;
+
+    /// Deletes a message
+    async fn delete_message(&self, channel: ChannelId, message: MessageId) -> Result<(), Error>;
+
+    /// Updates the delete button of the message which has been clicked
+    async fn update_delete_button(
+        &self,
+        interaction: &Self::Interaction,
+        current: usize,
+        needed: usize,
+    ) -> Result<(), Error>;
+
+    /// Shows another page in the message whose button has been clicked
+    async fn show_page(
+        &self,
+        interaction: &Self::Interaction,
+        page: PostMessage,
+    ) -> Result<(), Error>;
+
+    /// Tells discord a click is answered later, with a text only the user who clicked sees.
+    ///
+    /// Clicks have to be answered within a few seconds, which isn't enough to search a site.
+    async fn defer_privately(&self, interaction: &Self::Interaction) -> Result<(), Error>;
+
+    /// Shows a page only to the user who clicked, answering a deferred click
+    async fn show_private_page(
+        &self,
+        interaction: &Self::Interaction,
+        page: PostMessage,
+    ) -> Result<(), Error>;
+
+    /// Answers a deferred click with a text only the user who clicked sees
+    async fn reply_privately(
+        &self,
+        interaction: &Self::Interaction,
+        content: &str,
+    ) -> Result<(), Error>;
+
+    /// Acknowledges a click without changing the message
+    async fn acknowledge(&self, interaction: &Self::Interaction) -> Result<(), Error>;
+}
+
+/// Talks to discord through serenity's http client
+#[derive(Clone)]
+pub struct SerenityMessenger {
+    http: Arc<Http>,
+    /// downloads files which are uploaded again, like the ones of spoilered posts
+    downloads: reqwest::Client,
+}
+
+impl SerenityMessenger {
+    pub fn new(http: Arc<Http>, downloads: reqwest::Client) -> Self {
+        Self { http, downloads }
+    }
+
+    /// Reads or downloads the file of an attachment, unless it has more than `limit` bytes
+    async fn load(&self, attachment: &Attachment, limit: u64) -> Result<Option<Vec<u8>>, Error> {
+        match &attachment.source {
+            AttachmentSource::Path(path) => {
+                if tokio::fs::metadata(path).await?.len() > limit {
+                    return Ok(None);
+                }
+                Ok(Some(tokio::fs::read(path).await?))
+            }
+            AttachmentSource::Data(data) => {
+                if data.len() as u64 > limit {
+                    return Ok(None);
+                }
+                Ok(Some(data.to_vec()))
+            }
+            AttachmentSource::Url(url) => download(&self.downloads, url, limit).await,
+        }
+    }
+
+    /// Sends the messages of posts as one message, with the delete button and maybe page buttons
+    async fn send(
+        &self,
+        channel: ChannelId,
+        messages: Vec<PostMessage>,
+        paged: bool,
+    ) -> Result<MessageId, Error> {
+        let mut files = Vec::new();
+        let mut lines = Vec::new();
+        // discord limits the size of all files of a message together
+        let mut remaining = MAXIMUM_UPLOAD_SIZE;
+        for message in &messages {
+            lines.extend(message.content.clone());
+            let attachment = match &message.attachment {
+                Some(attachment) => attachment,
+                None => continue,
+            };
+            match self.load(attachment, remaining).await? {
+                Some(data) => {
+                    remaining -= data.len() as u64;
+                    files.push((data, &attachment.file_name));
+                }
+                None => match &attachment.oversized {
+                    Oversized::Fail => {
+                        return Err(Error::FileTooLarge(attachment.file_name.clone()))
+                    }
+                    Oversized::Skip => {}
+                    Oversized::Link(url) => lines.push(format!("||<{}>||", url)),
+                },
+            }
+        }
+        let content = lines.join("\n");
+        // reading ahead continues after the last page of the message
+        let read_ahead = messages.iter().rev().find_map(|message| message.read_ahead);
+        let embeds = messages
+            .iter()
+            .map(|message| message.embed.clone())
+            .collect();
+
+        let sent = channel
+            .send_message(&self.http, |m| {
+                if !content.is_empty() {
+                    m.content(&content);
+                }
+                // uploaded under the name of the attachment, which the embed may refer to
+                for (data, file_name) in &files {
+                    m.add_file(AttachmentType::Bytes {
+                        data: Cow::Borrowed(data.as_slice()),
+                        filename: file_name.to_string(),
+                    });
+                }
+                m.set_embeds(embeds).components(|c| {
+                    c.add_action_row(post_buttons(0, DELETE_VOTES_NEEDED));
+                    if paged {
+                        c.add_action_row(page_buttons());
+                    }
+                    if let Some(read_ahead) = read_ahead {
+                        c.add_action_row(read_ahead_button(read_ahead));
+                    }
+                    c
+                })
+            })
+            .await?;
+        Ok(sent.id)
+    }
+}
+
+/// The rows of buttons below the delete button of a message, built again from their custom ids
+fn other_buttons(message: &Message) -> Vec<CreateActionRow> {
+    message
+        .components
+        .iter()
+        .skip(1)
+        .filter_map(|row| {
+            let custom_id = row
+                .components
+                .iter()
+                .find_map(|component| match component {
+                    ActionRowComponent::Button(button) => button.custom_id.as_deref(),
+                    _ => None,
+                })?;
+            match custom_id {
+                "previous-page" | "next-page" => Some(page_buttons()),
+                _ => ReadAhead::from_custom_id(custom_id).map(read_ahead_button),
+            }
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Messenger for SerenityMessenger {
+    type Interaction = MessageComponentInteraction;
+
+    async fn send_post(
+        &self,
+        channel: ChannelId,
+        message: PostMessage,
+    ) -> Result<MessageId, Error> {
+        self.send(channel, vec![message], false).await
+    }
+
+    async fn send_gallery(
+        &self,
+        channel: ChannelId,
+        messages: Vec<PostMessage>,
+    ) -> Result<MessageId, Error> {
+        self.send(channel, messages, false).await
+    }
+
+    async fn send_pages(&self, channel: ChannelId, page: PostMessage) -> Result<MessageId, Error> {
+        self.send(channel, vec![page], true).await
+    }
+
+    async fn say(&self, channel: ChannelId, content: &str) -> Result<(), Error> {
+        channel.say(&self.http, content).await?;
+        Ok(())
+    }
+
+    async fn delete_message(&self, channel: ChannelId, message: MessageId) -> Result<(), Error> {
+        self.http.delete_message(channel.0, message.0).await?;
+        Ok(())
+    }
+
+    async fn update_delete_button(
+        &self,
+        interaction: &Self::Interaction,
+        current: usize,
+        needed: usize,
+    ) -> Result<(), Error> {
+        // the other buttons of the message are kept
+        let mut action_rows = vec![post_buttons(current, needed)];
+        action_rows.extend(other_buttons(&interaction.message));
+
+        interaction
+            .create_interaction_response(&self.http, |resp| {
+                resp.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|resp_data| {
+                        resp_data.components(|c| c.set_action_rows(action_rows))
+                    })
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn show_page(
+        &self,
+        interaction: &Self::Interaction,
+        page: PostMessage,
+    ) -> Result<(), Error> {
+        // the buttons are left as they are
+        interaction
+            .create_interaction_response(&self.http, |resp| {
+                resp.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|resp_data| {
+                        resp_data
+                            .content(page.content.unwrap_or_default())
+                            .set_embed(page.embed)
+                    })
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn defer_privately(&self, interaction: &Self::Interaction) -> Result<(), Error> {
+        interaction
+            .create_interaction_response(&self.http, |resp| {
+                resp.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                    .interaction_response_data(|resp_data| resp_data.ephemeral(true))
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn show_private_page(
+        &self,
+        interaction: &Self::Interaction,
+        page: PostMessage,
+    ) -> Result<(), Error> {
+        // the deferred response is already only visible to the user who clicked
+        interaction
+            .edit_original_interaction_response(&self.http, |resp| {
+                if let Some(content) = &page.content {
+                    resp.content(content);
+                }
+                if let Some(read_ahead) = page.read_ahead {
+                    resp.components(|c| c.add_action_row(read_ahead_button(read_ahead)));
+                }
+                resp.set_embed(page.embed)
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn reply_privately(
+        &self,
+        interaction: &Self::Interaction,
+        content: &str,
+    ) -> Result<(), Error> {
+        interaction
+            .edit_original_interaction_response(&self.http, |resp| resp.content(content))
+            .await?;
+        Ok(())
+    }
+
+    async fn acknowledge(&self, interaction: &Self::Interaction) -> Result<(), Error> {
+        interaction
+            .create_interaction_response(&self.http, |resp| {
+                resp.kind(InteractionResponseType::DeferredUpdateMessage)
+                    .interaction_response_data(|resp_data| resp_data)
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// Records everything instead of sending it, for the unit tests
+#[cfg(test)]
+pub(crate) mod recording {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use poise::serenity_prelude::{ChannelId, MessageId};
+
+    use super::Messenger;
+    use crate::{
+        utils::{Attachment, PostMessage},
+        Error,
+    };
+
+    /// Everything which would have been sent to discord
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(crate) enum Sent {
+        Post {
+            channel: ChannelId,
+            content: Option<String>,
+            attachment: Option<Attachment>,
+        },
+        Gallery {
+            channel: ChannelId,
+            contents: Vec<Option<String>>,
+        },
+        Pages {
+            channel: ChannelId,
+            content: Option<String>,
+        },
+        Say {
+            channel: ChannelId,
+            content: String,
+        },
+        Delete {
+            channel: ChannelId,
+            message: MessageId,
+        },
+        UpdateDeleteButton {
+            interaction: u64,
+            current: usize,
+            needed: usize,
+        },
+        ShowPage {
+            interaction: u64,
+            content: Option<String>,
+        },
+        PrivatePage {
+            interaction: u64,
+            content: Option<String>,
+        },
+        PrivateReply {
+            interaction: u64,
+            content: String,
+        },
+        Acknowledge {
+            interaction: u64,
+        },
+        Defer {
+            interaction: u64,
+        },
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct RecordingMessenger {
+        sent: Arc<Mutex<Vec<Sent>>>,
+    }
+
+    impl RecordingMessenger {
+        /// Everything sent so far, in order
+        pub(crate) fn sent(&self) -> Vec<Sent> {
+            self.sent.lock().unwrap().clone()
+        }
+
+        fn record(&self, sent: Sent) -> usize {
+            let mut all_sent = self.sent.lock().unwrap();
+            all_sent.push(sent);
+            all_sent.len()
+        }
+    }
+
+    #[async_trait]
+    impl Messenger for RecordingMessenger {
+        /// the id of the interaction
+        type Interaction = u64;
+
+        async fn send_post(
+            &self,
+            channel: ChannelId,
+            message: PostMessage,
+        ) -> Result<MessageId, Error> {
+            let count = self.record(Sent::Post {
+                channel,
+                content: message.content,
+                attachment: message.attachment,
+            });
+            Ok(MessageId(count as u64))
+        }
+
+        async fn send_gallery(
+            &self,
+            channel: ChannelId,
+            messages: Vec<PostMessage>,
+        ) -> Result<MessageId, Error> {
+            let count = self.record(Sent::Gallery {
+                channel,
+                contents: messages
+                    .into_iter()
+                    .map(|message| message.content)
+                    .collect(),
+            });
+            Ok(MessageId(count as u64))
+        }
+
+        async fn send_pages(
+            &self,
+            channel: ChannelId,
+            page: PostMessage,
+        ) -> Result<MessageId, Error> {
+            let count = self.record(Sent::Pages {
+                channel,
+                content: page.content,
+            });
+            Ok(MessageId(count as u64))
+        }
+
+        async fn say(&self, channel: ChannelId, content: &str) -> Result<(), Error> {
+            self.record(Sent::Say {
+                channel,
+                content: content.to_string(),
+            });
+            Ok(())
+        }
+
+        async fn delete_message(
+            &self,
+            channel: ChannelId,
+            message: MessageId,
+        ) -> Result<(), Error> {
+            self.record(Sent::Delete { channel, message });
+            Ok(())
+        }
+
+        async fn update_delete_button(
+            &self,
+            interaction: &u64,
+            current: usize,
+            needed: usize,
+        ) -> Result<(), Error> {
+            self.record(Sent::UpdateDeleteButton {
+                interaction: *interaction,
+                current,
+                needed,
+            });
+            Ok(())
+        }
+
+        async fn show_page(&self, interaction: &u64, page: PostMessage) -> Result<(), Error> {
+            self.record(Sent::ShowPage {
+                interaction: *interaction,
+                content: page.content,
+            });
+            Ok(())
+        }
+
+        async fn defer_privately(&self, interaction: &u64) -> Result<(), Error> {
+            self.record(Sent::Defer {
+                interaction: *interaction,
+            });
+            Ok(())
+        }
+
+        async fn show_private_page(
+            &self,
+            interaction: &u64,
+            page: PostMessage,
+        ) -> Result<(), Error> {
+            self.record(Sent::PrivatePage {
+                interaction: *interaction,
+                content: page.content,
+            });
+            Ok(())
+        }
+
+        async fn reply_privately(&self, interaction: &u64, content: &str) -> Result<(), Error> {
+            self.record(Sent::PrivateReply {
+                interaction: *interaction,
+                content: content.to_string(),
+            });
+            Ok(())
+        }
+
+        async fn acknowledge(&self, interaction: &u64) -> Result<(), Error> {
+            self.record(Sent::Acknowledge {
+                interaction: *interaction,
+            });
+            Ok(())
+        }
+    }
+}
@@ -1,18 +1,26 @@
-use std::{collections::HashSet, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use fred::{
     self,
     clients::RedisClient,
     error::RedisErrorKind,
-    interfaces::{HashesInterface, SetsInterface},
+    interfaces::{HashesInterface, KeysInterface, ListInterface, SetsInterface},
     prelude::RedisError,
     types::{FromRedis, RedisKey, RedisValue},
 };
 use poise::serenity_prelude::{ChannelId, GuildId, MessageId, RoleId};
 
 use crate::{
-    configuration::{ChannelConfiguration, GuildConfiguration, NsfwMode, TimeoutMode},
-    constants::{REDIS_PATH_SEPARATOR as SEP, REDIS_PREFIX},
+    configuration::{
+        ChannelConfiguration, DuplicateCheck, EmbedLayout, GuildConfiguration, MediaMode, NsfwMode,
+        PoolProgress, PostFormat, SourceKind, TagSet, TimeoutMode,
+    },
+    constants::{
+        DEFAULT_GALLERY_SIZE, POST_HISTORY_SIZE, REDIS_PATH_SEPARATOR as SEP, REDIS_PREFIX,
+    },
 };
 
 pub async fn known_guild_ids(redis: &RedisClient) -> Result<Vec<GuildId>, RedisError> {
@@ -30,6 +38,15 @@ pub async fn known_guild_ids(redis: &RedisClient) -> Result<Vec<GuildId>, RedisE
         .collect()
 }
 
+/// Artists whose posts are never sent in any guild
+pub async fn blocked_artists(redis: &RedisClient) -> Result<Vec<String>, RedisError> {
+    let mut artists: Vec<String> = redis
+        .smembers(format!("{REDIS_PREFIX}{SEP}BLOCKED_ARTISTS"))
+        .await?;
+    artists.sort();
+    Ok(artists)
+}
+
 pub async fn known_channel_ids(
     redis: &RedisClient,
     guild: GuildId,
@@ -86,16 +103,262 @@ pub async fn get_channel_config(
     config
 }
 
+pub async fn get_presets(
+    redis: &RedisClient,
+    guild: GuildId,
+) -> Result<HashMap<String, Vec<String>>, RedisError> {
+    let presets: HashMap<String, String> = redis
+        .hgetall(format!("{REDIS_PREFIX}{SEP}PRESETS{SEP}{guild}"))
+        .await?;
+    Ok(presets
+        .into_iter()
+        .map(|(name, tags)| (name, split_tags(&tags)))
+        .collect())
+}
+
+pub async fn get_tag_sets(
+    redis: &RedisClient,
+    channel: ChannelId,
+) -> Result<Vec<TagSet>, RedisError> {
+    let tag_sets: HashMap<String, String> = redis
+        .hgetall(format!("{REDIS_PREFIX}{SEP}TAG_SETS{SEP}{channel}"))
+        .await?;
+
+    let mut tag_sets = tag_sets
+        .into_iter()
+        .map(|(name, value)| {
+            let mut value = value.split_whitespace();
+            let weight = value
+                .next()
+                .and_then(|weight| weight.parse::<u32>().ok())
+                .ok_or_else(|| {
+                    RedisError::new(RedisErrorKind::Parse, "invalid weight for tag set")
+                })?;
+            let tags = value.map(|s| s.to_string()).collect();
+            Ok(TagSet { name, weight, tags })
+        })
+        .collect::<Result<Vec<TagSet>, RedisError>>()?;
+    tag_sets.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(tag_sets)
+}
+
+pub async fn set_known_guild_ids(
+    redis: &RedisClient,
+    guilds: &[GuildId],
+) -> Result<(), RedisError> {
+    let key = format!("{REDIS_PREFIX}{SEP}KNOWN_GUILDS");
+    redis.del::<(), _>(&key).await?;
+    if !guilds.is_empty() {
+        let guilds: Vec<String> = guilds.iter().map(|id| id.to_string()).collect();
+        redis.sadd::<(), _, _>(&key, guilds).await?;
+    }
+    Ok(())
+}
+
+pub async fn set_blocked_artists(
+    redis: &RedisClient,
+    artists: &[String],
+) -> Result<(), RedisError> {
+    let key = format!("{REDIS_PREFIX}{SEP}BLOCKED_ARTISTS");
+    redis.del::<(), _>(&key).await?;
+    if !artists.is_empty() {
+        redis.sadd::<(), _, _>(&key, artists.to_vec()).await?;
+    }
+    Ok(())
+}
+
+pub async fn set_known_channel_ids(
+    redis: &RedisClient,
+    guild: GuildId,
+    channels: &[ChannelId],
+) -> Result<(), RedisError> {
+    let key = format!("{REDIS_PREFIX}{SEP}KNOWN_CHANNELS{SEP}{guild}");
+    redis.del::<(), _>(&key).await?;
+    if !channels.is_empty() {
+        let channels: Vec<String> = channels.iter().map(|id| id.to_string()).collect();
+        redis.sadd::<(), _, _>(&key, channels).await?;
+    }
+    Ok(())
+}
+
+pub async fn set_guild_config(
+    redis: &RedisClient,
+    guild: GuildId,
+    config: &GuildConfiguration,
+) -> Result<(), RedisError> {
+    let moderator_roles: Vec<String> = config
+        .moderator_roles
+        .iter()
+        .map(|id| id.to_string())
+        .collect();
+
+    let key = format!("{REDIS_PREFIX}{SEP}GUILD_CONF{SEP}{guild}");
+
+    let mut values = HashMap::from([
+        ("moderator_roles", moderator_roles.join(" ")),
+        ("blacklist", config.blacklist.join(" ")),
+        ("blocked_artists", config.blocked_artists.join(" ")),
+    ]);
+
+    match config.log_channel {
+        Some(log_channel) => {
+            values.insert("log_channel", log_channel.to_string());
+        }
+        None => {
+            redis.hdel::<(), _, _>(&key, "log_channel").await?;
+        }
+    }
+
+    redis.hset::<(), _, _>(&key, values).await
+}
+
+pub async fn set_channel_config(
+    redis: &RedisClient,
+    channel: ChannelId,
+    config: &ChannelConfiguration,
+) -> Result<(), RedisError> {
+    let key = format!("{REDIS_PREFIX}{SEP}CHANNEL_CONF{SEP}{channel}");
+
+    let mut values = HashMap::from([
+        ("active", u8::from(config.active).to_string()),
+        ("timeout", config.timeout.to_string()),
+        ("timeout_mode", config.timeout_mode.to_string()),
+        ("nsfw_mode", config.nsfw_mode.to_string()),
+        ("source", config.source.to_string()),
+        ("media_mode", config.media_mode.to_string()),
+        ("embed_layout", config.embed_layout.to_string()),
+        ("duplicate_check", config.duplicate_check.to_string()),
+        ("post_format", config.post_format.to_string()),
+        ("gallery_size", config.gallery_size.to_string()),
+        ("tags", config.tags.join(" ")),
+        ("spoiler_tags", config.spoiler_tags.join(" ")),
+    ]);
+
+    match &config.preset {
+        Some(preset) => {
+            values.insert("preset", preset.clone());
+        }
+        None => {
+            redis.hdel::<(), _, _>(&key, "preset").await?;
+        }
+    }
+
+    match &config.pool {
+        Some(pool) => {
+            values.insert("pool_id", pool.pool_id.to_string());
+            values.insert("pool_page", pool.page.to_string());
+            match &pool.random_tags {
+                Some(tags) => {
+                    values.insert("pool_tags", tags.join(" "));
+                }
+                None => {
+                    redis.hdel::<(), _, _>(&key, "pool_tags").await?;
+                }
+            }
+        }
+        None => {
+            redis
+                .hdel::<(), _, _>(&key, vec!["pool_id", "pool_page", "pool_tags"])
+                .await?;
+        }
+    }
+
+    redis.hset::<(), _, _>(&key, values).await
+}
+
+pub async fn set_presets(
+    redis: &RedisClient,
+    guild: GuildId,
+    presets: &HashMap<String, Vec<String>>,
+) -> Result<(), RedisError> {
+    let key = format!("{REDIS_PREFIX}{SEP}PRESETS{SEP}{guild}");
+    redis.del::<(), _>(&key).await?;
+    if !presets.is_empty() {
+        let presets: HashMap<&str, String> = presets
+            .iter()
+            .map(|(name, tags)| (name.as_str(), tags.join(" ")))
+            .collect();
+        redis.hset::<(), _, _>(&key, presets).await?;
+    }
+    Ok(())
+}
+
+pub async fn set_tag_sets(
+    redis: &RedisClient,
+    channel: ChannelId,
+    tag_sets: &[TagSet],
+) -> Result<(), RedisError> {
+    let key = format!("{REDIS_PREFIX}{SEP}TAG_SETS{SEP}{channel}");
+    redis.del::<(), _>(&key).await?;
+    if !tag_sets.is_empty() {
+        let tag_sets: HashMap<&str, String> = tag_sets
+            .iter()
+            .map(|tag_set| {
+                (
+                    tag_set.name.as_str(),
+                    format!("{} {}", tag_set.weight, tag_set.tags.join(" ")),
+                )
+            })
+            .collect();
+        redis.hset::<(), _, _>(&key, tag_sets).await?;
+    }
+    Ok(())
+}
+
+/// Hashes of the files posted in a channel, the latest first
+pub async fn get_post_history(
+    redis: &RedisClient,
+    channel: ChannelId,
+) -> Result<Vec<String>, RedisError> {
+    redis
+        .lrange(format!("{REDIS_PREFIX}{SEP}HISTORY{SEP}{channel}"), 0, -1)
+        .await
+}
+
+pub async fn set_post_history(
+    redis: &RedisClient,
+    channel: ChannelId,
+    hashes: &[String],
+) -> Result<(), RedisError> {
+    let key = format!("{REDIS_PREFIX}{SEP}HISTORY{SEP}{channel}");
+    redis.del::<(), _>(&key).await?;
+    if !hashes.is_empty() {
+        redis.rpush::<(), _, _>(&key, hashes.to_vec()).await?;
+    }
+    Ok(())
+}
+
+/// Puts the hash of a file which has just been posted in front of the history of a channel.
+///
+/// Keeps the list like [PostHistory::remember](crate::history::PostHistory::remember) does.
+pub async fn push_post_history(
+    redis: &RedisClient,
+    channel: ChannelId,
+    md5: &str,
+) -> Result<(), RedisError> {
+    let key = format!("{REDIS_PREFIX}{SEP}HISTORY{SEP}{channel}");
+    redis.lrem::<(), _, _>(&key, 0, md5).await?;
+    redis.lpush::<(), _, _>(&key, md5).await?;
+    redis
+        .ltrim::<(), _>(&key, 0, POST_HISTORY_SIZE as i64 - 1)
+        .await
+}
+
+/// Tags are stored as a single string, separated by spaces
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split_whitespace().map(|s| s.to_string()).collect()
+}
+
 impl FromRedis for GuildConfiguration {
     fn from_value(value: RedisValue) -> Result<Self, RedisError> {
         let value = value.into_map()?;
+        // guilds which only have been stored for their presets don't have this key
         let moderator_roles = value
             .get(&RedisKey::from_static_str("moderator_roles"))
-            .ok_or_else(|| {
-                RedisError::new(RedisErrorKind::NotFound, "missing key: moderator_roles")
-            })?
-            .clone()
-            .convert::<String>()?;
+            .map(|moderator_roles| moderator_roles.clone().convert::<String>())
+            .transpose()?
+            .unwrap_or_default();
 
         let moderator_roles: Result<HashSet<RoleId>, RedisError> = moderator_roles
             .split_whitespace()
@@ -108,10 +371,39 @@ impl FromRedis for GuildConfiguration {
 
         let moderator_roles = moderator_roles?;
 
+        // guilds stored before the blacklist existed don't have this key
+        let blacklist = value
+            .get(&RedisKey::from_static_str("blacklist"))
+            .map(|blacklist| blacklist.clone().convert::<String>())
+            .transpose()?
+            .map(|blacklist| split_tags(&blacklist))
+            .unwrap_or_default();
+
+        let blocked_artists = value
+            .get(&RedisKey::from_static_str("blocked_artists"))
+            .map(|blocked_artists| blocked_artists.clone().convert::<String>())
+            .transpose()?
+            .map(|blocked_artists| split_tags(&blocked_artists))
+            .unwrap_or_default();
+
+        let log_channel = value
+            .get(&RedisKey::from_static_str("log_channel"))
+            .map(|log_channel| {
+                log_channel.as_u64().map(ChannelId).ok_or_else(|| {
+                    RedisError::new(RedisErrorKind::Parse, "invalid value for key: log_channel")
+                })
+            })
+            .transpose()?;
+
         Ok(Self {
             channels: Default::default(),
             moderator_roles,
+            blacklist,
+            blocked_artists,
+            presets: Default::default(),
+            log_channel,
             stop_signals: Default::default(),
+            reset_signals: Default::default(),
         })
     }
 }
@@ -148,18 +440,112 @@ impl FromRedis for ChannelConfiguration {
             .clone()
             .convert::<NsfwMode>()?;
 
+        // channels stored before other sites were supported don't have this key
+        let source = value
+            .get(&RedisKey::from_static_str("source"))
+            .map(|source| source.clone().convert::<SourceKind>())
+            .transpose()?
+            .unwrap_or_default();
+
+        let media_mode = value
+            .get(&RedisKey::from_static_str("media_mode"))
+            .map(|media_mode| media_mode.clone().convert::<MediaMode>())
+            .transpose()?
+            .unwrap_or_default();
+
+        let embed_layout = value
+            .get(&RedisKey::from_static_str("embed_layout"))
+            .map(|embed_layout| embed_layout.clone().convert::<EmbedLayout>())
+            .transpose()?
+            .unwrap_or_default();
+
+        let duplicate_check = value
+            .get(&RedisKey::from_static_str("duplicate_check"))
+            .map(|duplicate_check| duplicate_check.clone().convert::<DuplicateCheck>())
+            .transpose()?
+            .unwrap_or_default();
+
+        let post_format = value
+            .get(&RedisKey::from_static_str("post_format"))
+            .map(|post_format| post_format.clone().convert::<PostFormat>())
+            .transpose()?
+            .unwrap_or_default();
+
+        let gallery_size = value
+            .get(&RedisKey::from_static_str("gallery_size"))
+            .map(|gallery_size| {
+                gallery_size.as_u64().ok_or_else(|| {
+                    RedisError::new(RedisErrorKind::Parse, "invalid value for key: gallery_size")
+                })
+            })
+            .transpose()?
+            .unwrap_or(DEFAULT_GALLERY_SIZE);
+
         let tags = value
             .get(&RedisKey::from_static_str("tags"))
             .ok_or_else(|| RedisError::new(RedisErrorKind::NotFound, "missing key: tags"))?
             .clone()
-            .convert::<Vec<String>>()?;
+            .convert::<String>()?;
+        let tags = split_tags(&tags);
+
+        let spoiler_tags = value
+            .get(&RedisKey::from_static_str("spoiler_tags"))
+            .map(|spoiler_tags| spoiler_tags.clone().convert::<String>())
+            .transpose()?
+            .map(|spoiler_tags| split_tags(&spoiler_tags))
+            .unwrap_or_default();
+
+        let preset = value
+            .get(&RedisKey::from_static_str("preset"))
+            .map(|preset| preset.clone().convert::<String>())
+            .transpose()?;
+
+        let pool_id = value
+            .get(&RedisKey::from_static_str("pool_id"))
+            .map(|pool_id| {
+                pool_id.as_u64().ok_or_else(|| {
+                    RedisError::new(RedisErrorKind::Parse, "invalid value for key: pool_id")
+                })
+            })
+            .transpose()?;
+        let pool = match pool_id {
+            Some(pool_id) => {
+                let page = value
+                    .get(&RedisKey::from_static_str("pool_page"))
+                    .and_then(|page| page.as_u64())
+                    .ok_or_else(|| {
+                        RedisError::new(RedisErrorKind::Parse, "invalid value for key: pool_page")
+                    })?;
+                let random_tags = value
+                    .get(&RedisKey::from_static_str("pool_tags"))
+                    .map(|tags| tags.clone().convert::<String>())
+                    .transpose()?
+                    .map(|tags| split_tags(&tags));
+                Some(PoolProgress {
+                    pool_id,
+                    page: page as usize,
+                    random_tags,
+                })
+            }
+            None => None,
+        };
 
         Ok(Self {
             active,
             timeout,
             timeout_mode,
             nsfw_mode,
+            source,
+            media_mode,
+            embed_layout,
+            duplicate_check,
+            post_format,
+            gallery_size,
             tags,
+            preset,
+            tag_sets: Default::default(),
+            spoiler_tags,
+            pool,
         })
     }
 }
@@ -185,3 +571,58 @@ impl FromRedis for TimeoutMode {
         Ok(mode)
     }
 }
+
+impl FromRedis for SourceKind {
+    fn from_value(value: RedisValue) -> Result<Self, RedisError> {
+        let value = value
+            .as_str()
+            .ok_or_else(|| RedisError::new(RedisErrorKind::NotFound, "Source is not a string"))?;
+        let source = Self::from_str(&value)
+            .map_err(|e| RedisError::new(RedisErrorKind::Parse, e.to_string()))?;
+        Ok(source)
+    }
+}
+
+impl FromRedis for MediaMode {
+    fn from_value(value: RedisValue) -> Result<Self, RedisError> {
+        let value = value.as_str().ok_or_else(|| {
+            RedisError::new(RedisErrorKind::NotFound, "Media mode is not a string")
+        })?;
+        let mode = Self::from_str(&value)
+            .map_err(|e| RedisError::new(RedisErrorKind::Parse, e.to_string()))?;
+        Ok(mode)
+    }
+}
+
+impl FromRedis for EmbedLayout {
+    fn from_value(value: RedisValue) -> Result<Self, RedisError> {
+        let value = value.as_str().ok_or_else(|| {
+            RedisError::new(RedisErrorKind::NotFound, "Embed layout is not a string")
+        })?;
+        let layout = Self::from_str(&value)
+            .map_err(|e| RedisError::new(RedisErrorKind::Parse, e.to_string()))?;
+        Ok(layout)
+    }
+}
+
+impl FromRedis for DuplicateCheck {
+    fn from_value(value: RedisValue) -> Result<Self, RedisError> {
+        let value = value.as_str().ok_or_else(|| {
+            RedisError::new(RedisErrorKind::NotFound, "Duplicate check is not a string")
+        })?;
+        let check = Self::from_str(&value)
+            .map_err(|e| RedisError::new(RedisErrorKind::Parse, e.to_string()))?;
+        Ok(check)
+    }
+}
+
+impl FromRedis for PostFormat {
+    fn from_value(value: RedisValue) -> Result<Self, RedisError> {
+        let value = value.as_str().ok_or_else(|| {
+            RedisError::new(RedisErrorKind::NotFound, "Post format is not a string")
+        })?;
+        let format = Self::from_str(&value)
+            .map_err(|e| RedisError::new(RedisErrorKind::Parse, e.to_string()))?;
+        Ok(format)
+    }
+}
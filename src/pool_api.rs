@@ -0,0 +1,118 @@
+//! Access to e621 pools, whose pages are posted in order instead of searched for
+
+use serde::Deserialize;
+
+use crate::{
+    configuration::{NsfwMode, SourceKind},
+    constants::MAXIMUM_SEARCH_TAGS,
+    filter::{check_tag_limit, split_query},
+    http::HttpSettings,
+    Error,
+};
+
+/// `inpool:true` and `order:random`, which are added to the tags of random pools
+const RANDOM_POOL_METATAGS: usize = 2;
+
+/// A pool as returned by the e621 api
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoolInfo {
+    pub id: u64,
+    pub name: String,
+    /// the posts of the pool, in reading order
+    pub post_ids: Vec<u64>,
+}
+
+impl PoolInfo {
+    /// The name of the pool, with underscores shown as spaces
+    pub fn title(&self) -> String {
+        self.name.replace('_', " ")
+    }
+}
+
+/// Response of `/posts.json`, of which only the pools of the posts are needed
+#[derive(Deserialize)]
+struct PostsResponse {
+    posts: Vec<PostPools>,
+}
+
+#[derive(Deserialize)]
+struct PostPools {
+    pools: Vec<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolApi {
+    /// http client, shared between requests
+    http: reqwest::Client,
+    /// base url of the nsfw site, for example `https://e621.net`
+    e621_url: String,
+    /// base url of the sfw site, for example `https://e926.net`
+    e926_url: String,
+    /// login and api token
+    login: Option<(String, String)>,
+}
+
+impl PoolApi {
+    pub fn new(e621_url: &str, e926_url: &str, http: &HttpSettings) -> Result<Self, Error> {
+        let http = http.client()?;
+        Ok(Self {
+            http,
+            e621_url: e621_url.trim_end_matches('/').to_string(),
+            e926_url: e926_url.trim_end_matches('/').to_string(),
+            login: None,
+        })
+    }
+
+    pub fn login(&mut self, login: String, token: String) {
+        self.login = Some((login, token));
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(
+        &self,
+        nsfw_mode: NsfwMode,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, Error> {
+        let base_url = match nsfw_mode {
+            NsfwMode::SFW => &self.e926_url,
+            NsfwMode::NSFW => &self.e621_url,
+        };
+        let mut request = self.http.get(format!("{}{}", base_url, path)).query(query);
+        if let Some((login, token)) = &self.login {
+            request = request.basic_auth(login, Some(token));
+        }
+        Ok(request.send().await?.error_for_status()?.json().await?)
+    }
+
+    /// Looks up a pool by its id
+    pub async fn pool(&self, nsfw_mode: NsfwMode, id: u64) -> Result<PoolInfo, Error> {
+        self.get(nsfw_mode, &format!("/pools/{}.json", id), &[])
+            .await
+    }
+
+    /// Picks a random pool which contains a post matching the tags.
+    ///
+    /// Excluded tags which don't fit into the search are left out, as they only narrow
+    /// down which pools can be picked. Too many other tags are an error.
+    pub async fn random_pool(&self, nsfw_mode: NsfwMode, tags: &[String]) -> Result<u64, Error> {
+        let limit = MAXIMUM_SEARCH_TAGS - RANDOM_POOL_METATAGS;
+        check_tag_limit(tags, SourceKind::E621, limit).map_err(Error::TooManyTags)?;
+        let (mut tags, _) = split_query(tags.to_vec(), &[], limit);
+        tags.extend_from_slice(&["inpool:true".to_string(), "order:random".to_string()]);
+
+        let response: PostsResponse = self
+            .get(
+                nsfw_mode,
+                "/posts.json",
+                &[("tags", &tags.join(" ")), ("limit", "1")],
+            )
+            .await?;
+
+        response
+            .posts
+            .into_iter()
+            .flat_map(|post| post.pools)
+            .next()
+            .ok_or(Error::NoPoolFound)
+    }
+}
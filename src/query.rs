@@ -0,0 +1,276 @@
+//! Helpers for working with e621 search queries
+
+/// Metatags known to e621. Tags starting with one of these followed by a `:`
+/// are not looked up in the tag database.
+pub static METATAGS: &[&str] = &[
+    "approver",
+    "arttags",
+    "chartags",
+    "comm",
+    "commenter",
+    "copytags",
+    "date",
+    "delreason",
+    "description",
+    "downvotes",
+    "duration",
+    "favcount",
+    "fav",
+    "filesize",
+    "filetype",
+    "gentags",
+    "height",
+    "id",
+    "invtags",
+    "ischild",
+    "isparent",
+    "lortags",
+    "md5",
+    "mpixels",
+    "noteupdater",
+    "order",
+    "parent",
+    "pool",
+    "randseed",
+    "rating",
+    "ratio",
+    "score",
+    "set",
+    "source",
+    "spectags",
+    "status",
+    "tagcount",
+    "type",
+    "upvotes",
+    "user",
+    "votedown",
+    "voteup",
+    "width",
+];
+
+/// Metatags which take a number or a range of numbers like `>5` or `10..20`
+static NUMERIC_METATAGS: &[&str] = &[
+    "arttags",
+    "chartags",
+    "copytags",
+    "downvotes",
+    "duration",
+    "favcount",
+    "gentags",
+    "height",
+    "id",
+    "invtags",
+    "lortags",
+    "mpixels",
+    "ratio",
+    "score",
+    "spectags",
+    "tagcount",
+    "upvotes",
+    "width",
+];
+
+/// Values `order:` can be sorted by. All of them can be suffixed with `_asc` or `_desc`
+static ORDER_VALUES: &[&str] = &[
+    "aspect_ratio",
+    "change",
+    "comment_count",
+    "comments",
+    "created_at",
+    "duration",
+    "favcount",
+    "filesize",
+    "hot",
+    "id",
+    "landscape",
+    "mpixels",
+    "note",
+    "portrait",
+    "random",
+    "rank",
+    "score",
+    "tagcount",
+    "updated",
+];
+
+/// Values `rating:` accepts
+static RATING_VALUES: &[&str] = &["s", "q", "e", "safe", "questionable", "explicit"];
+
+/// How a tag inside a search query is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagKind {
+    /// Posts must have this tag
+    Required,
+    /// Posts must not have this tag, written as `-tag`
+    Excluded,
+    /// Posts must have at least one of the optional tags, written as `~tag`
+    Optional,
+    /// A metatag like `score:>5` or `rating:s`
+    Meta,
+}
+
+/// Figure out how a tag is used in a query
+pub fn tag_kind(tag: &str) -> TagKind {
+    if is_metatag(tag_name(tag)) {
+        TagKind::Meta
+    } else if tag.starts_with('-') {
+        TagKind::Excluded
+    } else if tag.starts_with('~') {
+        TagKind::Optional
+    } else {
+        TagKind::Required
+    }
+}
+
+/// Strips the `-` and `~` prefixes from a tag
+pub fn tag_name(tag: &str) -> &str {
+    tag.trim_start_matches(|c| c == '-' || c == '~')
+}
+
+/// Check if a tag (without prefix) is a metatag
+pub fn is_metatag(name: &str) -> bool {
+    name.split_once(':')
+        .map(|(prefix, _)| METATAGS.contains(&prefix.to_lowercase().as_str()))
+        .unwrap_or_default()
+}
+
+/// Returns the `-` or `~` prefix of a tag, if any
+pub fn tag_prefix(tag: &str) -> &str {
+    &tag[..tag.len() - tag_name(tag).len()]
+}
+
+/// Checks the value of a metatag (without prefix), returning a description of the problem
+pub fn validate_metatag(name: &str) -> Result<(), String> {
+    let (key, value) = name
+        .split_once(':')
+        .ok_or_else(|| format!("`{}` is not a metatag", name))?;
+    let key = key.to_lowercase();
+    let value = value.to_lowercase();
+
+    if value.is_empty() {
+        return Err(format!("`{}` needs a value", key));
+    }
+
+    if key == "rating" && !RATING_VALUES.contains(&value.as_str()) {
+        return Err(format!(
+            "`{}` is not a valid rating. Use one of: {}",
+            value,
+            RATING_VALUES.join(", ")
+        ));
+    }
+
+    if key == "order" {
+        let order = value
+            .strip_suffix("_asc")
+            .or_else(|| value.strip_suffix("_desc"))
+            .unwrap_or(&value);
+        if !ORDER_VALUES.contains(&order) {
+            return Err(format!("`{}` is not a valid order", value));
+        }
+    }
+
+    if NUMERIC_METATAGS.contains(&key.as_str()) && !is_numeric_range(&value) {
+        return Err(format!(
+            "`{}` is not a valid number or range for `{}`",
+            value, key
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check if a value is a number or a range like `>5`, `<=3`, `1..10`, `..10`
+fn is_numeric_range(value: &str) -> bool {
+    // `inf` and `nan` parse as floats too, but e621 doesn't understand them
+    let is_number = |s: &str| s.parse::<f64>().map_or(false, f64::is_finite);
+
+    if let Some((start, end)) = value.split_once("..") {
+        return (start.is_empty() || is_number(start))
+            && (end.is_empty() || is_number(end))
+            && !(start.is_empty() && end.is_empty());
+    }
+
+    let number = value
+        .strip_prefix(">=")
+        .or_else(|| value.strip_prefix("<="))
+        .or_else(|| value.strip_prefix('>'))
+        .or_else(|| value.strip_prefix('<'))
+        .unwrap_or(value);
+    is_number(number)
+}
+
+/// Returns the names of all tags in a query which can be looked up in the tag database.
+///
+/// This excludes metatags and wildcard searches.
+pub fn lookup_names(tags: &[String]) -> Vec<&str> {
+    tags.iter()
+        .filter(|tag| tag_kind(tag) != TagKind::Meta)
+        .map(|tag| tag_name(tag))
+        .filter(|name| !name.is_empty() && !name.contains('*'))
+        .collect()
+}
+
+/// Levenshtein distance between two strings, used to suggest tags for typos
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_numbers_and_ranges() {
+        for value in ["5", "1.5", ">5", ">=5", "<3", "<=3", "1..10", "..10", "1.."] {
+            assert!(is_numeric_range(value), "{} should be valid", value);
+        }
+    }
+
+    #[test]
+    fn rejects_broken_ranges() {
+        for value in [
+            "", "..", ">=", "<", "a", "1..b", "a..1", ">..5", "1...", "inf", "nan",
+        ] {
+            assert!(!is_numeric_range(value), "{} should be invalid", value);
+        }
+    }
+
+    #[test]
+    fn validates_metatags() {
+        assert!(validate_metatag("score:>=10").is_ok());
+        assert!(validate_metatag("rating:s").is_ok());
+        assert!(validate_metatag("Rating:Explicit").is_ok());
+        assert!(validate_metatag("order:score_asc").is_ok());
+        assert!(validate_metatag("order:random").is_ok());
+        assert!(validate_metatag("source:none").is_ok());
+
+        assert!(validate_metatag("score:").is_err());
+        assert!(validate_metatag("score:lots").is_err());
+        assert!(validate_metatag("rating:x").is_err());
+        assert!(validate_metatag("order:score_up").is_err());
+        assert!(validate_metatag("pikachu").is_err());
+    }
+
+    #[test]
+    fn measures_edit_distance() {
+        assert_eq!(edit_distance("pikachu", "pikachu"), 0);
+        assert_eq!(edit_distance("pikachu", "pikach"), 1);
+        assert_eq!(edit_distance("pikachu", "pikaxhu"), 1);
+        assert_eq!(edit_distance("pikachu", "pikachuu"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("pokémon", "pokemon"), 1);
+    }
+}
@@ -1,8 +1,17 @@
 #![allow(unused_imports)]
 
-use std::{fmt::Debug, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use fred::{
     clients::RedisClient,
     interfaces::ClientLike,
@@ -11,22 +20,43 @@ use fred::{
 };
 
 use poise::{
-    futures_util::{FutureExt, StreamExt},
+    futures_util::{FutureExt, StreamExt, TryStreamExt},
     serenity_prelude::{ChannelId, Context, GuildId, MessageId, Ready},
     Framework,
 };
 use rand::Rng;
-use rs621::{client::Client, post::Post};
 use tokio::{
     sync::watch::{self, Sender},
     time::sleep,
 };
-use tracing::{debug, info, instrument};
+use tracing::{debug, info, instrument, warn};
 
 use crate::{
-    configuration::{GuildConfiguration, NsfwMode, TimeoutMode},
-    persistence::{get_channel_config, get_guild_config, known_channel_ids, known_guild_ids},
-    tasks::{delete_button_listener, send_images_loop},
+    configuration::{
+        ChannelConfiguration, DuplicateCheck, EmbedLayout, GuildConfiguration, MediaMode, NsfwMode,
+        PoolProgress, PostFormat, SourceKind, TagSet, TimeoutMode,
+    },
+    constants::{
+        DEFAULT_E621_URL, DEFAULT_E926_URL, DEFAULT_GALLERY_SIZE, DUPLICATE_SEARCH_ATTEMPTS,
+    },
+    filter::{allows_rating, merge_excluded_tags, split_query, PostFilter},
+    history::{hash_file, PostHistory},
+    http::HttpSettings,
+    messenger::SerenityMessenger,
+    persistence::{
+        blocked_artists, get_channel_config, get_guild_config, get_post_history, get_presets,
+        get_tag_sets, known_channel_ids, known_guild_ids, push_post_history, set_blocked_artists,
+        set_channel_config, set_guild_config, set_known_channel_ids, set_known_guild_ids,
+        set_post_history, set_presets, set_tag_sets,
+    },
+    pool_api::{PoolApi, PoolInfo},
+    sources::{
+        danbooru::DanbooruSource, e621::E621Source, find_new_post, gelbooru::GelbooruSource,
+        local::LocalSource, Post, PostSource, Sources,
+    },
+    tag_api::TagApi,
+    tasks::{button_listener, send_images_loop, Galleries, TaskState},
+    utils::{MessageStyle, ReadAhead},
     Error,
 };
 
@@ -34,10 +64,18 @@ use crate::{
 pub struct Data {
     /// configurations for all known guilds
     guild_configurations: Arc<DashMap<GuildId, GuildConfiguration>>,
-    /// nsfw client
-    e621_client: Arc<Client>,
-    /// sfw client
-    e926_client: Arc<Client>,
+    /// runtime state of the posting loop of every channel
+    task_states: Arc<DashMap<ChannelId, TaskState>>,
+    /// artists whose posts are never sent in any guild, managed by the bot owners
+    blocked_artists: Arc<DashSet<String>>,
+    /// clients for the sites posts are fetched from
+    sources: Arc<Sources>,
+    /// client for the e621 tag database
+    tag_api: Arc<TagApi>,
+    /// client for e621 pools
+    pool_api: Arc<PoolApi>,
+    /// client for files which are downloaded to upload them to discord
+    downloads: reqwest::Client,
     /// serenity context
     context: Context,
     /// redis db handle
@@ -46,16 +84,20 @@ pub struct Data {
     /// will be switched to true, signaling the shutdown functions
     /// to run
     shutdown_sender: Arc<Sender<bool>>,
-    // post history
-    // post_history: Arc<DashMap<MessageId, u64>>,
+    /// hashes of the files posted in every channel
+    post_histories: Arc<DashMap<ChannelId, PostHistory>>,
+    /// the pages of the messages which can be flipped through
+    galleries: Galleries,
 }
 
 impl Debug for Data {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Data")
             .field("guild_configurations", &self.guild_configurations)
-            .field("e621_client", &self.e621_client)
-            .field("e926_client", &self.e926_client)
+            .field("task_states", &self.task_states)
+            .field("sources", &self.sources)
+            .field("tag_api", &self.tag_api)
+            .field("pool_api", &self.pool_api)
             //.field("context", &self.context)
             .finish()
     }
@@ -63,24 +105,51 @@ impl Debug for Data {
 
 impl Data {
     async fn new(context: Context, shutdown_sender: Sender<bool>) -> Result<Self, crate::Error> {
-        let user_agent = "CutePokebot/0.1.0 (norom)";
-
-        let (e6_client, e9_client) =
-            if let (Ok(login), Ok(token)) = (dotenv::var("E6_LOGIN"), dotenv::var("E6_TOKEN")) {
-                info!("Using logged in clients with user {}", &login);
-                let mut e6_client = Client::new("https://e621.net", &user_agent)?;
-                e6_client.login(login.clone(), token.clone());
-
-                let mut e9_client = Client::new("https://e926.net", &user_agent)?;
-                e9_client.login(login, token);
-                (e6_client, e9_client)
-            } else {
-                info!("Using logged out clients");
-                (
-                    Client::new("https://e621.net", &user_agent)?,
-                    Client::new("https://e926.net", &user_agent)?,
-                )
-            };
+        let http = HttpSettings::from_env();
+        info!("Using user agent {}", http.user_agent);
+
+        // can be pointed somewhere else, for example at a mock server
+        let e621_url = dotenv::var("E621_URL").unwrap_or_else(|_| DEFAULT_E621_URL.to_string());
+        let e926_url = dotenv::var("E926_URL").unwrap_or_else(|_| DEFAULT_E926_URL.to_string());
+
+        let e6_login = dotenv::var("E6_LOGIN")
+            .ok()
+            .zip(dotenv::var("E6_TOKEN").ok());
+        let mut tag_api = TagApi::new(&e621_url, &http)?;
+        let mut pool_api = PoolApi::new(&e621_url, &e926_url, &http)?;
+        if let Some((login, token)) = e6_login.clone() {
+            info!("Using logged in e621 clients with user {}", &login);
+            tag_api.login(login.clone(), token.clone());
+            pool_api.login(login, token);
+        } else {
+            info!("Using logged out e621 clients");
+        }
+
+        // the other sites work without logging in, but may limit what can be searched for
+        let danbooru_login = dotenv::var("DANBOORU_LOGIN")
+            .ok()
+            .zip(dotenv::var("DANBOORU_API_KEY").ok());
+        let gelbooru_login = dotenv::var("GELBOORU_USER_ID")
+            .ok()
+            .zip(dotenv::var("GELBOORU_API_KEY").ok());
+
+        let sources = Sources {
+            e621: E621Source::new(&e621_url, &e926_url, &http, e6_login)?,
+            danbooru: DanbooruSource::new(
+                "https://danbooru.donmai.us",
+                "https://safebooru.donmai.us",
+                &http,
+                danbooru_login,
+            )?,
+            gelbooru: GelbooruSource::new(
+                "https://gelbooru.com",
+                Some("rating:general"),
+                &http,
+                gelbooru_login,
+            )?,
+            safebooru: GelbooruSource::new("https://safebooru.org", None, &http, None)?,
+            local: LocalSource::new(dotenv::var("LOCAL_IMAGES_DIR").ok().map(PathBuf::from)),
+        };
 
         let redis = async {
             let config = RedisConfig::default();
@@ -94,27 +163,69 @@ impl Data {
 
         Ok(Self {
             guild_configurations: Arc::new(DashMap::new()),
-            e621_client: Arc::new(e6_client),
-            e926_client: Arc::new(e9_client),
+            task_states: Arc::new(DashMap::new()),
+            blocked_artists: Arc::new(DashSet::new()),
+            post_histories: Arc::new(DashMap::new()),
+            galleries: Galleries::default(),
+            sources: Arc::new(sources),
+            tag_api: Arc::new(tag_api),
+            pool_api: Arc::new(pool_api),
+            downloads: http.client()?,
             context,
             redis,
             shutdown_sender: Arc::new(shutdown_sender),
         })
     }
 
-    pub(crate) async fn store_to_db(&self) -> Result<(), crate::Error> {
+    pub async fn store_to_db(&self) -> Result<(), crate::Error> {
+        // don't hold on to the map while talking to redis
+        let guild_configurations: Vec<(GuildId, GuildConfiguration)> = self
+            .guild_configurations
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().snapshot()))
+            .collect();
+
+        set_blocked_artists(&self.redis, &self.blocked_artists()).await?;
+
+        let guild_ids: Vec<GuildId> = guild_configurations.iter().map(|(id, _)| *id).collect();
+        set_known_guild_ids(&self.redis, &guild_ids).await?;
+
+        for (guild_id, guild_conf) in guild_configurations {
+            set_guild_config(&self.redis, guild_id, &guild_conf).await?;
+            set_presets(&self.redis, guild_id, guild_conf.presets()).await?;
+
+            let channel_ids: Vec<ChannelId> = guild_conf.channels.keys().copied().collect();
+            set_known_channel_ids(&self.redis, guild_id, &channel_ids).await?;
+
+            for (channel_id, channel_conf) in guild_conf.channels.iter() {
+                set_channel_config(&self.redis, *channel_id, channel_conf).await?;
+                set_tag_sets(&self.redis, *channel_id, &channel_conf.tag_sets).await?;
+
+                let hashes: Vec<String> = self
+                    .post_histories
+                    .get(channel_id)
+                    .map(|history| history.hashes().cloned().collect())
+                    .unwrap_or_default();
+                set_post_history(&self.redis, *channel_id, &hashes).await?;
+            }
+        }
         Ok(())
     }
 
     pub(crate) async fn restore_from_db(&self) -> Result<(), crate::Error> {
+        self.set_blocked_artists(blocked_artists(&self.redis).await?);
+
         for guild_id in known_guild_ids(&self.redis).await? {
             let mut guild_conf = get_guild_config(&self.redis, guild_id).await?;
+            guild_conf.presets = get_presets(&self.redis, guild_id).await?;
 
             for channel_id in known_channel_ids(&self.redis, guild_id).await? {
-                guild_conf.insert(
-                    channel_id,
-                    get_channel_config(&self.redis, channel_id).await?,
-                );
+                let mut channel_conf = get_channel_config(&self.redis, channel_id).await?;
+                channel_conf.tag_sets = get_tag_sets(&self.redis, channel_id).await?;
+                let hashes = get_post_history(&self.redis, channel_id).await?;
+                self.post_histories
+                    .insert(channel_id, PostHistory::new(hashes));
+                guild_conf.insert(channel_id, channel_conf);
             }
 
             self.guild_configurations.insert(guild_id, guild_conf);
@@ -125,14 +236,15 @@ impl Data {
     pub async fn start(&self, guild: GuildId, channel: ChannelId, delay: Option<u64>) {
         let mut entry = self.guild_configurations.entry(guild).or_default();
         if !entry.is_active(channel) {
-            let (tx, rx) = watch::channel(false);
-            entry.start(channel, tx);
+            let (stop_tx, stop_rx) = watch::channel(false);
+            let (reset_tx, reset_rx) = watch::channel(());
+            entry.start(channel, stop_tx, reset_tx);
             let self_clone = self.clone();
             tokio::spawn(async move {
                 if let Some(delay) = delay {
                     sleep(Duration::from_secs(delay)).await;
                 }
-                send_images_loop(self_clone, guild, channel, rx).await;
+                send_images_loop(self_clone, guild, channel, stop_rx, reset_rx).await;
             });
             info!("Started sending images to {}", channel);
         }
@@ -148,6 +260,20 @@ impl Data {
         info!("Requesting task for {} to be stopped", channel);
     }
 
+    /// Restart the timer of the sending task in a channel (inside the guild)
+    ///
+    /// Returns false if no task is running for that channel
+    pub async fn reset_timer(&self, guild: GuildId, channel: ChannelId) -> bool {
+        let reset = self
+            .guild_configurations
+            .get(&guild)
+            .map(|config| config.reset_timer(channel))
+            .unwrap_or_default();
+
+        info!("Requesting timer for {} to be reset: {}", channel, reset);
+        reset
+    }
+
     /// Starts all tasks marked active.
     ///
     /// This function is supposed to be called only once,
@@ -241,6 +367,390 @@ impl Data {
             .set_nsfw_mode(channel, nsfw_mode);
     }
 
+    /// Get the site posts are fetched from for a channel in a guild
+    pub async fn source(&self, guild: GuildId, channel: ChannelId) -> Option<SourceKind> {
+        let source = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.source(&channel));
+        debug!("{:?}", source);
+        source
+    }
+
+    /// Set the site posts are fetched from for a channel in a guild
+    pub async fn set_source(&self, guild: GuildId, channel: ChannelId, source: SourceKind) {
+        debug!("{:?}", source);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_source(channel, source);
+    }
+
+    /// Get the kinds of files posted in a channel in a guild
+    pub async fn media_mode(&self, guild: GuildId, channel: ChannelId) -> Option<MediaMode> {
+        let media_mode = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.media_mode(&channel));
+        debug!("{:?}", media_mode);
+        media_mode
+    }
+
+    /// Set the kinds of files posted in a channel in a guild
+    pub async fn set_media_mode(&self, guild: GuildId, channel: ChannelId, media_mode: MediaMode) {
+        debug!("{:?}", media_mode);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_media_mode(channel, media_mode);
+    }
+
+    /// Get how posts are shown in a channel in a guild
+    pub async fn embed_layout(&self, guild: GuildId, channel: ChannelId) -> Option<EmbedLayout> {
+        let embed_layout = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.embed_layout(&channel));
+        debug!("{:?}", embed_layout);
+        embed_layout
+    }
+
+    /// Set how posts are shown in a channel in a guild
+    pub async fn set_embed_layout(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        embed_layout: EmbedLayout,
+    ) {
+        debug!("{:?}", embed_layout);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_embed_layout(channel, embed_layout);
+    }
+
+    /// Get where a channel in a guild looks for files which have been posted before
+    pub async fn duplicate_check(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+    ) -> Option<DuplicateCheck> {
+        let duplicate_check = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.duplicate_check(&channel));
+        debug!("{:?}", duplicate_check);
+        duplicate_check
+    }
+
+    /// Set where a channel in a guild looks for files which have been posted before
+    pub async fn set_duplicate_check(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        duplicate_check: DuplicateCheck,
+    ) {
+        debug!("{:?}", duplicate_check);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_duplicate_check(channel, duplicate_check);
+    }
+
+    /// Remember the file of a post which has been sent in a channel, so it can be skipped later.
+    ///
+    /// The history is stored right away, so it survives the bot being killed.
+    pub async fn remember_post(&self, channel: ChannelId, md5: String) {
+        self.post_histories
+            .entry(channel)
+            .or_default()
+            .remember(md5.clone());
+        if let Err(err) = push_post_history(&self.redis, channel, &md5).await {
+            warn!("Could not store the post history of {}: {}", channel, err);
+        }
+    }
+
+    /// Get the hashes of the files which must not be posted again in a channel in a guild
+    async fn known_hashes(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        duplicate_check: DuplicateCheck,
+    ) -> HashSet<String> {
+        let channels = match duplicate_check {
+            DuplicateCheck::Off => Vec::new(),
+            DuplicateCheck::Channel => vec![channel],
+            DuplicateCheck::Guild => self.channels(guild).await,
+        };
+
+        let mut hashes = HashSet::new();
+        for channel in channels {
+            if let Some(history) = self.post_histories.get(&channel) {
+                hashes.extend(history.hashes().cloned());
+            }
+        }
+        hashes
+    }
+
+    /// Get the tags whose posts are sent behind a spoiler in a channel in a guild
+    pub async fn spoiler_tags(&self, guild: GuildId, channel: ChannelId) -> Option<Vec<String>> {
+        let spoiler_tags = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.spoiler_tags(&channel).cloned());
+        debug!("{:?}", spoiler_tags);
+        spoiler_tags
+    }
+
+    /// Set the tags whose posts are sent behind a spoiler in a channel in a guild
+    pub async fn set_spoiler_tags(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        spoiler_tags: Vec<String>,
+    ) {
+        debug!("{:?}", spoiler_tags);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_spoiler_tags(channel, spoiler_tags);
+    }
+
+    /// Get everything about how posts are shown in a channel in a guild
+    pub async fn message_style(&self, guild: GuildId, channel: ChannelId) -> MessageStyle {
+        MessageStyle {
+            layout: self.embed_layout(guild, channel).await.unwrap_or_default(),
+            spoiler_tags: self.spoiler_tags(guild, channel).await.unwrap_or_default(),
+            format: self.post_format(guild, channel).await.unwrap_or_default(),
+            gallery_size: self
+                .gallery_size(guild, channel)
+                .await
+                .unwrap_or(DEFAULT_GALLERY_SIZE),
+            pool: None,
+        }
+    }
+
+    /// Get if a channel in a guild gets its posts one at a time or several at once
+    pub async fn post_format(&self, guild: GuildId, channel: ChannelId) -> Option<PostFormat> {
+        let post_format = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.post_format(&channel));
+        debug!("{:?}", post_format);
+        post_format
+    }
+
+    /// Set if a channel in a guild gets its posts one at a time or several at once
+    pub async fn set_post_format(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        post_format: PostFormat,
+    ) {
+        debug!("{:?}", post_format);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_post_format(channel, post_format);
+    }
+
+    /// Get how far a channel in a guild has read the pool it is reading, if any
+    pub async fn pool_progress(&self, guild: GuildId, channel: ChannelId) -> Option<PoolProgress> {
+        let pool_progress = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.pool(&channel));
+        debug!("{:?}", pool_progress);
+        pool_progress
+    }
+
+    /// Set the pool a channel in a guild reads, or `None` to go back to searching for posts
+    pub async fn set_pool_progress(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        pool_progress: Option<PoolProgress>,
+    ) {
+        debug!("{:?}", pool_progress);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_pool(channel, pool_progress);
+    }
+
+    /// Set the next page of a pool to post in a channel in a guild,
+    /// unless the channel has switched to another pool in the meantime
+    pub async fn set_pool_page(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        pool_id: u64,
+        page: usize,
+    ) {
+        if let Some(mut pool_progress) = self.pool_progress(guild, channel).await {
+            if pool_progress.pool_id == pool_id {
+                pool_progress.page = page;
+                self.set_pool_progress(guild, channel, Some(pool_progress))
+                    .await;
+            }
+        }
+    }
+
+    /// Looks up a pool on the site of the nsfw mode of a channel in a guild
+    pub async fn pool(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        pool_id: u64,
+    ) -> Result<PoolInfo, Error> {
+        let nsfw_mode = self.nsfw_mode(guild, channel).await.unwrap_or_default();
+        self.pool_api.pool(nsfw_mode, pool_id).await
+    }
+
+    /// Picks a random pool for a channel in a guild, and starts reading it
+    pub async fn read_random_pool(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        tags: Vec<String>,
+    ) -> Result<PoolInfo, Error> {
+        let nsfw_mode = self.nsfw_mode(guild, channel).await.unwrap_or_default();
+        let pool_id = self.pool_api.random_pool(nsfw_mode, &tags).await?;
+        let pool = self.pool_api.pool(nsfw_mode, pool_id).await?;
+
+        self.set_pool_progress(
+            guild,
+            channel,
+            Some(PoolProgress {
+                pool_id,
+                page: 0,
+                random_tags: Some(tags),
+            }),
+        )
+        .await;
+        Ok(pool)
+    }
+
+    /// Moves a channel in a guild on from a pool whose pages have all been posted.
+    ///
+    /// Pools which have been picked randomly are followed by another random pool,
+    /// which is returned. Otherwise, or if no other pool has been found, the channel
+    /// goes back to searching for posts.
+    pub async fn finish_pool(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+    ) -> Result<Option<PoolInfo>, Error> {
+        let random_tags = self
+            .pool_progress(guild, channel)
+            .await
+            .and_then(|pool_progress| pool_progress.random_tags);
+
+        if let Some(tags) = random_tags {
+            match self.read_random_pool(guild, channel, tags).await {
+                Ok(pool) => return Ok(Some(pool)),
+                Err(Error::NoPoolFound) => {
+                    info!("No other pool has been found for {}", channel)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        self.set_pool_progress(guild, channel, None).await;
+        Ok(None)
+    }
+
+    /// Gets the next page of a pool which can be posted in a channel in a guild,
+    /// starting at `next_page`.
+    ///
+    /// Pages which have been deleted, aren't safe in sfw mode or have excluded tags
+    /// are skipped. `next_page` is moved past the returned page.
+    pub async fn pool_page(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        pool: &PoolInfo,
+        next_page: &AtomicUsize,
+    ) -> Result<Post, Error> {
+        let nsfw_mode = self.nsfw_mode(guild, channel).await.unwrap_or_default();
+        let mut filter = PostFilter::default();
+        for tag in self.excluded_tags(guild, SourceKind::E621).await {
+            filter.exclude(&tag);
+        }
+
+        loop {
+            let page = next_page.load(Ordering::Relaxed);
+            let post_id = *pool.post_ids.get(page).ok_or(Error::PoolFinished)?;
+            // pages which could not be fetched are tried again
+            let post = self.sources.e621.post(nsfw_mode, post_id).await?;
+            next_page.store(page + 1, Ordering::Relaxed);
+
+            match post {
+                Some(post) if filter.allows(&post) => return Ok(post),
+                Some(_) => debug!(
+                    "Skipping page {} of pool #{} in {}, it has excluded tags",
+                    page + 1,
+                    pool.id,
+                    channel
+                ),
+                None => debug!(
+                    "Skipping page {} of pool #{} in {}, #{} can not be found",
+                    page + 1,
+                    pool.id,
+                    channel,
+                    post_id
+                ),
+            }
+        }
+    }
+
+    /// Gets the page of a pool after a post, for reading ahead in a channel in a guild.
+    ///
+    /// Returns `None` after the last page, or if the post isn't part of the pool anymore
+    pub async fn page_after(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        read_ahead: ReadAhead,
+    ) -> Result<Option<Post>, Error> {
+        let pool = self.pool(guild, channel, read_ahead.pool_id).await?;
+        let page = match pool
+            .post_ids
+            .iter()
+            .position(|id| *id == read_ahead.post_id)
+        {
+            Some(page) => page + 1,
+            None => return Ok(None),
+        };
+
+        match self
+            .pool_page(guild, channel, &pool, &AtomicUsize::new(page))
+            .await
+        {
+            Ok(post) => Ok(Some(post)),
+            Err(Error::PoolFinished) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Get how many posts a channel in a guild gets at once by the gallery and pages formats
+    pub async fn gallery_size(&self, guild: GuildId, channel: ChannelId) -> Option<u64> {
+        let gallery_size = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.gallery_size(&channel));
+        debug!("{:?}", gallery_size);
+        gallery_size
+    }
+
+    /// Set how many posts a channel in a guild gets at once by the gallery and pages formats
+    pub async fn set_gallery_size(&self, guild: GuildId, channel: ChannelId, gallery_size: u64) {
+        debug!("{:?}", gallery_size);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_gallery_size(channel, gallery_size);
+    }
+
     /// Get the timeout mode for a channel in a guild
     pub async fn timeout_mode(&self, guild: GuildId, channel: ChannelId) -> Option<TimeoutMode> {
         let timeout_mode = self
@@ -268,32 +778,349 @@ impl Data {
             .set_timeout_mode(channel, timeout_mode);
     }
 
+    /// Get the blacklist of a guild
+    pub async fn blacklist(&self, guild: GuildId) -> Vec<String> {
+        let blacklist = self
+            .guild_configurations
+            .get(&guild)
+            .map(|c| c.blacklist().clone())
+            .unwrap_or_default();
+        debug!("{:?}", blacklist);
+        blacklist
+    }
+
+    /// Get the channel errors are reported in for a guild
+    pub async fn log_channel(&self, guild: GuildId) -> Option<ChannelId> {
+        let log_channel = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.log_channel());
+        debug!("{:?}", log_channel);
+        log_channel
+    }
+
+    /// Set the channel errors are reported in for a guild
+    pub async fn set_log_channel(&self, guild: GuildId, log_channel: Option<ChannelId>) {
+        debug!("{:?}", log_channel);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_log_channel(log_channel);
+    }
+
+    /// Set the blacklist of a guild
+    pub async fn set_blacklist(&self, guild: GuildId, blacklist: Vec<String>) {
+        debug!("{:?}", blacklist);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_blacklist(blacklist);
+    }
+
+    /// Get the artists whose posts are never sent in a guild
+    pub async fn guild_blocked_artists(&self, guild: GuildId) -> Vec<String> {
+        let blocked_artists = self
+            .guild_configurations
+            .get(&guild)
+            .map(|c| c.blocked_artists().clone())
+            .unwrap_or_default();
+        debug!("{:?}", blocked_artists);
+        blocked_artists
+    }
+
+    /// Set the artists whose posts are never sent in a guild
+    pub async fn set_guild_blocked_artists(&self, guild: GuildId, blocked_artists: Vec<String>) {
+        debug!("{:?}", blocked_artists);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_blocked_artists(blocked_artists);
+    }
+
+    /// Get the artists whose posts are never sent in any guild, sorted by name
+    pub fn blocked_artists(&self) -> Vec<String> {
+        let mut blocked_artists: Vec<String> = self
+            .blocked_artists
+            .iter()
+            .map(|artist| artist.key().clone())
+            .collect();
+        blocked_artists.sort();
+        blocked_artists
+    }
+
+    /// Set the artists whose posts are never sent in any guild
+    pub fn set_blocked_artists(&self, blocked_artists: Vec<String>) {
+        debug!("{:?}", blocked_artists);
+        self.blocked_artists.clear();
+        blocked_artists.into_iter().for_each(|artist| {
+            self.blocked_artists.insert(artist);
+        });
+    }
+
+    /// Get every tag excluded in all channels of a guild which search `source`.
+    ///
+    /// See [merge_excluded_tags] for the order of the tags.
+    pub async fn excluded_tags(&self, guild: GuildId, source: SourceKind) -> Vec<String> {
+        merge_excluded_tags(
+            source,
+            self.blocked_artists()
+                .into_iter()
+                .chain(self.guild_blocked_artists(guild).await),
+            self.blacklist(guild).await,
+        )
+    }
+
+    /// Get the tag sets for a channel in a guild
+    pub async fn tag_sets(&self, guild: GuildId, channel: ChannelId) -> Option<Vec<TagSet>> {
+        let tag_sets = self
+            .guild_configurations
+            .get(&guild)
+            .and_then(|c| c.tag_sets(&channel).cloned());
+        debug!("{:?}", tag_sets);
+        tag_sets
+    }
+
+    /// Set the tag sets for a channel in a guild
+    pub async fn set_tag_sets(&self, guild: GuildId, channel: ChannelId, tag_sets: Vec<TagSet>) {
+        debug!("{:?}", tag_sets);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .set_tag_sets(channel, tag_sets);
+    }
+
+    /// Get all presets of a guild
+    pub async fn presets(&self, guild: GuildId) -> HashMap<String, Vec<String>> {
+        self.guild_configurations
+            .get(&guild)
+            .map(|c| c.presets().clone())
+            .unwrap_or_default()
+    }
+
+    /// Get the name of the preset a channel is linked to
+    pub async fn preset(&self, guild: GuildId, channel: ChannelId) -> Option<String> {
+        self.guild_configurations
+            .get(&guild)
+            .and_then(|c| c.preset(&channel).cloned())
+    }
+
+    /// Create or overwrite a preset of a guild
+    pub async fn save_preset(&self, guild: GuildId, name: String, tags: Vec<String>) {
+        debug!("{}: {:?}", name, tags);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .save_preset(name, tags);
+    }
+
+    /// Link a channel to a preset. Returns false if there is no such preset
+    pub async fn apply_preset(&self, guild: GuildId, channel: ChannelId, name: &str) -> bool {
+        debug!("{}/{}: {}", guild, channel, name);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .apply_preset(channel, name)
+    }
+
+    /// Delete a preset of a guild. Returns false if there is no such preset
+    pub async fn delete_preset(&self, guild: GuildId, name: &str) -> bool {
+        debug!("{}", name);
+        self.guild_configurations
+            .entry(guild)
+            .or_default()
+            .delete_preset(name)
+    }
+
+    /// Check if the posting loop is running for a channel in a guild
+    pub async fn is_active(&self, guild: GuildId, channel: ChannelId) -> bool {
+        self.guild_configurations
+            .get(&guild)
+            .map(|c| c.is_active(channel))
+            .unwrap_or_default()
+    }
+
+    /// Get a copy of the configuration for a channel in a guild
+    pub async fn channel_configuration(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+    ) -> Option<ChannelConfiguration> {
+        self.guild_configurations
+            .get(&guild)
+            .and_then(|c| c.channels.get(&channel).cloned())
+    }
+
+    /// Get all configured channels of a guild
+    pub async fn channels(&self, guild: GuildId) -> Vec<ChannelId> {
+        self.guild_configurations
+            .get(&guild)
+            .map(|c| c.channels.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Get a copy of the posting loop state for a channel
+    pub fn task_state(&self, channel: ChannelId) -> Option<TaskState> {
+        self.task_states.get(&channel).map(|s| s.clone())
+    }
+
+    /// Update the posting loop state for a channel
+    pub fn update_task_state(&self, channel: ChannelId, f: impl FnOnce(&mut TaskState)) {
+        f(&mut self.task_states.entry(channel).or_default());
+    }
+
+    /// Picks the tags a channel in a guild is searched by.
+    ///
+    /// If the channel has tag sets, one of them is picked by weight and its name
+    /// is returned with its tags. Otherwise the tags of the channel are used.
+    pub async fn pick_tags(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+    ) -> Result<(Option<String>, Vec<String>), Error> {
+        let tag_set = self.guild_configurations.get(&guild).and_then(|c| {
+            c.channels
+                .get(&channel)
+                .and_then(|c| c.pick_tag_set().cloned())
+        });
+
+        match tag_set {
+            Some(tag_set) => {
+                debug!("Using tag set {} for {}", tag_set.name, channel);
+                Ok((Some(tag_set.name), tag_set.tags))
+            }
+            None => {
+                let tags = self.tags(guild, channel).await.ok_or(Error::NoTagsSet)?;
+                Ok((None, tags))
+            }
+        }
+    }
+
     /// Get's a random post according to the configuration of the given channel
     /// inside the given guild
+    ///
+    /// If the channel has tag sets, one of them is picked by weight. Tag sets are
+    /// only counted by the posting loop, once their posts have been sent.
     pub async fn get_post(&self, guild: GuildId, channel: ChannelId) -> Result<Post, Error> {
-        let client = match self.nsfw_mode(guild, channel).await.unwrap_or_default() {
-            NsfwMode::SFW => self.e926_client.clone(),
-            NsfwMode::NSFW => self.e621_client.clone(),
-        };
+        let (_, tags) = self.pick_tags(guild, channel).await?;
+        self.get_post_with_tags(guild, channel, tags).await
+    }
 
-        let mut tags = self.tags(guild, channel).await.ok_or(Error::NoTagsSet)?;
-        tags.extend_from_slice(&["order:random".to_string(), "limit:20".to_string()]);
+    /// Get's a random post for the given tags, using the rest of the configuration
+    /// of the given channel inside the given guild
+    ///
+    /// Posts whose file has been posted before are skipped, as far as the channel's
+    /// [DuplicateCheck] asks for it. Files of sites which don't tell their md5 are hashed,
+    /// so the returned post always has one unless the check is off.
+    pub async fn get_post_with_tags(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        tags: Vec<String>,
+    ) -> Result<Post, Error> {
+        let nsfw_mode = self.nsfw_mode(guild, channel).await.unwrap_or_default();
+        let source_kind = self.source(guild, channel).await.unwrap_or_default();
+        let source = self.post_source(source_kind);
+        let media_mode = self.media_mode(guild, channel).await.unwrap_or_default();
+        let excluded = self.excluded_tags(guild, source_kind).await;
+        let duplicate_check = self
+            .duplicate_check(guild, channel)
+            .await
+            .unwrap_or_default();
+        let known_hashes = self.known_hashes(guild, channel, duplicate_check).await;
 
-        let mut post_search = Box::pin(
-            client
-                .post_search(&tags[..])
-                .filter_map(|a| async { Result::ok(a) }),
-        );
+        for attempt in 1..=DUPLICATE_SEARCH_ATTEMPTS {
+            let mut post = find_new_post(
+                source,
+                nsfw_mode,
+                media_mode,
+                tags.clone(),
+                &excluded,
+                &known_hashes,
+            )
+            .await?;
+
+            if duplicate_check == DuplicateCheck::Off {
+                return Ok(post);
+            }
+
+            if post.md5.is_none() {
+                // rather posted twice than not at all
+                if let Err(err) = hash_file(&self.downloads, &mut post).await {
+                    warn!("Could not hash #{}: {}", post.id, err);
+                }
+            }
 
-        let post = post_search.next().await;
+            match &post.md5 {
+                Some(md5) if known_hashes.contains(md5) => debug!(
+                    "Skipping #{} in {}, it has been posted before ({}/{})",
+                    post.id, channel, attempt, DUPLICATE_SEARCH_ATTEMPTS
+                ),
+                _ => return Ok(post),
+            }
+        }
+
+        Err(Error::Uhhh(
+            "Only posts which have been posted before have been found".to_string(),
+        ))
+    }
+
+    /// Searches a site for up to `limit` posts, returning any error that occurs.
+    ///
+    /// Posts with tags excluded in the guild are left out, like when posting.
+    pub async fn search_posts(
+        &self,
+        guild: GuildId,
+        source: SourceKind,
+        nsfw_mode: NsfwMode,
+        tags: Vec<String>,
+        limit: usize,
+    ) -> Result<Vec<Post>, Error> {
+        let post_source = self.post_source(source);
+        let excluded = self.excluded_tags(guild, source).await;
+        let (tags, filter) = split_query(tags, &excluded, post_source.tag_limit());
+
+        let posts = post_source
+            .search(nsfw_mode, &tags)
+            .try_filter(|post| {
+                futures::future::ready(allows_rating(nsfw_mode, post) && filter.allows(post))
+            })
+            .take(limit)
+            .try_collect()
+            .await?;
+        Ok(posts)
+    }
 
-        post.ok_or_else(|| Error::Uhhh("No posts this time...".to_string()))
+    /// Get the client for a site posts are fetched from
+    pub fn post_source(&self, source: SourceKind) -> &dyn PostSource {
+        self.sources.get(source)
+    }
+
+    /// Get a reference to the client for the e621 tag database
+    pub fn tag_api(&self) -> &TagApi {
+        &self.tag_api
+    }
+
+    /// Get a reference to the client for e621 pools
+    pub fn pool_api(&self) -> &PoolApi {
+        &self.pool_api
     }
 
     /// Get a reference to the data's serenity context.
     pub fn context(&self) -> &Context {
         &self.context
     }
+
+    /// Get a messenger sending to discord through the data's serenity context.
+    ///
+    /// Files of spoilered posts are downloaded with the data's http settings
+    pub fn messenger(&self) -> SerenityMessenger {
+        SerenityMessenger::new(self.context.http.clone(), self.downloads.clone())
+    }
+
+    /// Get the pages of the messages which can be flipped through
+    pub fn galleries(&self) -> Galleries {
+        self.galleries.clone()
+    }
 }
 
 /// called by the main function, sets up everything and runs the background tasks
@@ -306,6 +1133,6 @@ pub async fn setup<U, E>(
     let data = Data::new(context.clone(), shutdown_sender).await?;
     data.restore_from_db().await?;
     data.start_all().await;
-    let _ = tokio::spawn(delete_button_listener(context.clone()));
+    let _ = tokio::spawn(button_listener(context.clone(), data.clone()));
     Ok(data)
 }
@@ -0,0 +1,184 @@
+//! Danbooru and its safe mirror, accessed through their json api
+
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use poise::serenity_prelude::Timestamp;
+use serde::Deserialize;
+
+use crate::{
+    configuration::NsfwMode,
+    http::HttpSettings,
+    sources::{split_tag_string, Post, PostScore, PostSource, PostTags, Rating},
+    Error,
+};
+
+/// Danbooru only allows 2 tags per search for anonymous and regular accounts
+const TAG_LIMIT: usize = 2;
+
+/// Most posts Danbooru returns per page
+const PAGE_LIMIT: usize = 200;
+
+/// A post as returned by `/posts.json`.
+///
+/// Posts which are restricted for the account lack most of their fields.
+#[derive(Debug, Deserialize)]
+struct DanbooruPost {
+    id: u64,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    score: i64,
+    #[serde(default)]
+    up_score: i64,
+    #[serde(default)]
+    down_score: i64,
+    #[serde(default)]
+    fav_count: u64,
+    #[serde(default)]
+    rating: Option<String>,
+    #[serde(default)]
+    md5: Option<String>,
+    #[serde(default)]
+    file_ext: String,
+    #[serde(default)]
+    file_url: Option<String>,
+    #[serde(default)]
+    large_file_url: Option<String>,
+    #[serde(default)]
+    preview_file_url: Option<String>,
+    #[serde(default)]
+    tag_string_general: String,
+    #[serde(default)]
+    tag_string_character: String,
+    #[serde(default)]
+    tag_string_copyright: String,
+    #[serde(default)]
+    tag_string_artist: String,
+    #[serde(default)]
+    tag_string_meta: String,
+    #[serde(default)]
+    source: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DanbooruSource {
+    /// http client, shared between requests
+    http: reqwest::Client,
+    /// base url of the full site, for example `https://danbooru.donmai.us`
+    nsfw_url: String,
+    /// base url of the safe mirror, for example `https://safebooru.donmai.us`
+    sfw_url: String,
+    /// login and api key
+    login: Option<(String, String)>,
+}
+
+impl DanbooruSource {
+    pub fn new(
+        nsfw_url: &str,
+        sfw_url: &str,
+        http: &HttpSettings,
+        login: Option<(String, String)>,
+    ) -> Result<Self, Error> {
+        let http = http.client()?;
+        Ok(Self {
+            http,
+            nsfw_url: nsfw_url.trim_end_matches('/').to_string(),
+            sfw_url: sfw_url.trim_end_matches('/').to_string(),
+            login,
+        })
+    }
+
+    /// Fetches one page of random posts
+    async fn random_posts(&self, base_url: &str, tags: &[String]) -> Result<Vec<Post>, Error> {
+        let tags = tags.join(" ");
+        let limit = PAGE_LIMIT.to_string();
+        let mut request = self.http.get(format!("{}/posts.json", base_url)).query(&[
+            ("tags", tags.as_str()),
+            ("limit", limit.as_str()),
+            ("random", "true"),
+        ]);
+        if let Some((login, api_key)) = &self.login {
+            request = request.basic_auth(login, Some(api_key));
+        }
+
+        let posts: Vec<DanbooruPost> = request.send().await?.error_for_status()?.json().await?;
+
+        Ok(posts
+            .into_iter()
+            .map(|post| convert_post(post, base_url))
+            .collect())
+    }
+}
+
+impl PostSource for DanbooruSource {
+    fn tag_limit(&self) -> usize {
+        TAG_LIMIT
+    }
+
+    fn result_limit(&self) -> usize {
+        PAGE_LIMIT
+    }
+
+    fn search<'a>(
+        &'a self,
+        nsfw_mode: NsfwMode,
+        tags: &'a [String],
+    ) -> BoxStream<'a, Result<Post, Error>> {
+        let base_url = match nsfw_mode {
+            NsfwMode::SFW => &self.sfw_url,
+            NsfwMode::NSFW => &self.nsfw_url,
+        };
+
+        stream::once(async move {
+            let posts = self.random_posts(base_url, tags).await?;
+            Ok(stream::iter(posts.into_iter().map(Ok::<Post, Error>)))
+        })
+        .try_flatten()
+        .boxed()
+    }
+}
+
+fn convert_post(post: DanbooruPost, base_url: &str) -> Post {
+    Post {
+        id: post.id,
+        url: Some(format!("{}/posts/{}", base_url, post.id)),
+        title: None,
+        file_url: post.file_url,
+        file_path: None,
+        file_data: None,
+        file_ext: post.file_ext,
+        md5: post.md5,
+        sample_url: post.large_file_url,
+        preview_url: post.preview_file_url,
+        // restricted posts don't tell their rating, so they are never treated as safe
+        rating: post
+            .rating
+            .map(|rating| Rating::from_booru(&rating))
+            .unwrap_or(Rating::Explicit),
+        tags: PostTags {
+            general: split_tag_string(&post.tag_string_general),
+            character: split_tag_string(&post.tag_string_character),
+            copyright: split_tag_string(&post.tag_string_copyright),
+            artist: split_tag_string(&post.tag_string_artist),
+            meta: split_tag_string(&post.tag_string_meta),
+            ..Default::default()
+        },
+        score: PostScore {
+            up: post.up_score,
+            down: post.down_score,
+            total: post.score,
+        },
+        fav_count: post.fav_count,
+        description: String::new(),
+        sources: if post.source.is_empty() {
+            Vec::new()
+        } else {
+            vec![post.source]
+        },
+        created_at: post
+            .created_at
+            .and_then(|created_at| Timestamp::parse(&created_at).ok()),
+    }
+}
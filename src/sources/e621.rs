@@ -0,0 +1,137 @@
+//! e621 and its safe counterpart e926, accessed through rs621
+
+use futures::{stream::BoxStream, StreamExt};
+use poise::serenity_prelude::Timestamp;
+use rs621::{client::Client, post::PostRating};
+
+use crate::{
+    configuration::NsfwMode,
+    constants::{MAXIMUM_SEARCH_TAGS, RESERVED_SEARCH_TAGS},
+    http::HttpSettings,
+    sources::{Post, PostScore, PostSource, PostTags, Rating},
+    Error,
+};
+
+#[derive(Debug)]
+pub struct E621Source {
+    /// nsfw client
+    e621_client: Client,
+    /// sfw client
+    e926_client: Client,
+    /// base url of the nsfw site, used for links to posts
+    e621_url: String,
+    /// base url of the sfw site, used for links to posts
+    e926_url: String,
+}
+
+impl E621Source {
+    pub fn new(
+        e621_url: &str,
+        e926_url: &str,
+        http: &HttpSettings,
+        login: Option<(String, String)>,
+    ) -> Result<Self, Error> {
+        let mut e621_client = Client::new(e621_url, http.user_agent.as_str())?;
+        let mut e926_client = Client::new(e926_url, http.user_agent.as_str())?;
+        if let Some((login, token)) = login {
+            e621_client.login(login.clone(), token.clone());
+            e926_client.login(login, token);
+        }
+
+        Ok(Self {
+            e621_client,
+            e926_client,
+            e621_url: e621_url.trim_end_matches('/').to_string(),
+            e926_url: e926_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Get the e6 or e9 client and its url, depending on the nsfw mode
+    fn client(&self, nsfw_mode: NsfwMode) -> (&Client, &str) {
+        match nsfw_mode {
+            NsfwMode::SFW => (&self.e926_client, &self.e926_url),
+            NsfwMode::NSFW => (&self.e621_client, &self.e621_url),
+        }
+    }
+
+    /// Looks up a post by its id, like the pages of a pool.
+    ///
+    /// Returns `None` for deleted posts, and in sfw mode for posts which aren't safe
+    pub async fn post(&self, nsfw_mode: NsfwMode, id: u64) -> Result<Option<Post>, Error> {
+        let (client, base_url) = self.client(nsfw_mode);
+        let tags = [format!("id:{}", id)];
+
+        let post = client.post_search(&tags[..]).boxed().next().await;
+        Ok(post.transpose()?.map(|post| convert_post(post, base_url)))
+    }
+}
+
+impl PostSource for E621Source {
+    fn tag_limit(&self) -> usize {
+        MAXIMUM_SEARCH_TAGS - RESERVED_SEARCH_TAGS
+    }
+
+    fn result_limit(&self) -> usize {
+        // rs621 keeps fetching pages for as long as there are results
+        usize::MAX
+    }
+
+    fn search<'a>(
+        &'a self,
+        nsfw_mode: NsfwMode,
+        tags: &'a [String],
+    ) -> BoxStream<'a, Result<Post, Error>> {
+        let (client, base_url) = self.client(nsfw_mode);
+
+        let mut tags = tags.to_vec();
+        tags.extend_from_slice(&["order:random".to_string(), "limit:20".to_string()]);
+
+        client
+            .post_search(&tags[..])
+            .map(move |post| {
+                post.map(|post| convert_post(post, base_url))
+                    .map_err(Error::from)
+            })
+            .boxed()
+    }
+}
+
+/// Converts a post as returned by rs621
+fn convert_post(post: rs621::post::Post, base_url: &str) -> Post {
+    Post {
+        id: post.id,
+        url: Some(format!("{}/posts/{}", base_url, post.id)),
+        title: None,
+        file_url: post.file.url,
+        file_path: None,
+        file_data: None,
+        file_ext: post.file.ext,
+        md5: Some(post.file.md5),
+        sample_url: post.sample.url,
+        preview_url: post.preview.url,
+        rating: match post.rating {
+            PostRating::Safe => Rating::Safe,
+            PostRating::Questionable => Rating::Questionable,
+            PostRating::Explicit => Rating::Explicit,
+        },
+        tags: PostTags {
+            general: post.tags.general,
+            species: post.tags.species,
+            character: post.tags.character,
+            copyright: post.tags.copyright,
+            artist: post.tags.artist,
+            invalid: post.tags.invalid,
+            lore: post.tags.lore,
+            meta: post.tags.meta,
+        },
+        score: PostScore {
+            up: post.score.up,
+            down: post.score.down,
+            total: post.score.total,
+        },
+        fav_count: post.fav_count,
+        description: post.description,
+        sources: post.sources,
+        created_at: Timestamp::from_unix_timestamp(post.created_at.timestamp()).ok(),
+    }
+}
@@ -0,0 +1,194 @@
+//! Gelbooru and sites running the same software, like Safebooru
+
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use serde::Deserialize;
+
+use crate::{
+    configuration::NsfwMode,
+    http::HttpSettings,
+    sources::{split_tag_string, Post, PostScore, PostSource, PostTags, Rating},
+    Error,
+};
+
+/// Most posts Gelbooru returns per page
+const PAGE_LIMIT: usize = 100;
+
+/// Gelbooru and Safebooru don't document a tag limit, so searches are kept as short as e621's
+const TAG_LIMIT: usize = 40;
+
+/// `sort:random` and the rating tag of sfw searches, which are added by the bot
+const ADDED_TAGS: usize = 2;
+
+/// A post as returned by the dapi. Tags are not split into categories.
+#[derive(Debug, Deserialize)]
+struct GelbooruPost {
+    id: u64,
+    /// called `hash` by older versions
+    #[serde(default, alias = "hash")]
+    md5: Option<String>,
+    #[serde(default)]
+    image: String,
+    #[serde(default)]
+    file_url: Option<String>,
+    #[serde(default)]
+    sample_url: Option<String>,
+    #[serde(default)]
+    preview_url: Option<String>,
+    #[serde(default)]
+    rating: String,
+    #[serde(default)]
+    score: Option<i64>,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    source: String,
+}
+
+/// Response of the dapi. Newer versions wrap the posts in an object
+/// and leave them out if nothing has been found.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GelbooruResponse {
+    Posts(Vec<GelbooruPost>),
+    Wrapped {
+        #[serde(default)]
+        post: Vec<GelbooruPost>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct GelbooruSource {
+    /// http client, shared between requests
+    http: reqwest::Client,
+    /// base url, for example `https://gelbooru.com`
+    base_url: String,
+    /// query tag restricting searches to safe posts, if the site has anything else
+    sfw_tag: Option<String>,
+    /// user id and api key
+    login: Option<(String, String)>,
+}
+
+impl GelbooruSource {
+    pub fn new(
+        base_url: &str,
+        sfw_tag: Option<&str>,
+        http: &HttpSettings,
+        login: Option<(String, String)>,
+    ) -> Result<Self, Error> {
+        let http = http.client()?;
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            sfw_tag: sfw_tag.map(|tag| tag.to_string()),
+            login,
+        })
+    }
+
+    /// Fetches one page of random posts
+    async fn random_posts(&self, tags: &[String]) -> Result<Vec<Post>, Error> {
+        let tags = tags.join(" ");
+        let limit = PAGE_LIMIT.to_string();
+        let mut request = self
+            .http
+            .get(format!("{}/index.php", self.base_url))
+            .query(&[
+                ("page", "dapi"),
+                ("s", "post"),
+                ("q", "index"),
+                ("json", "1"),
+                ("tags", tags.as_str()),
+                ("limit", limit.as_str()),
+            ]);
+        if let Some((user_id, api_key)) = &self.login {
+            request = request.query(&[("user_id", user_id), ("api_key", api_key)]);
+        }
+
+        let body = request.send().await?.error_for_status()?.text().await?;
+        // older versions answer with nothing at all if nothing has been found
+        if body.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let posts = match serde_json::from_str(&body)? {
+            GelbooruResponse::Posts(posts) => posts,
+            GelbooruResponse::Wrapped { post } => post,
+        };
+
+        Ok(posts
+            .into_iter()
+            .map(|post| convert_post(post, &self.base_url))
+            .collect())
+    }
+}
+
+impl PostSource for GelbooruSource {
+    fn tag_limit(&self) -> usize {
+        TAG_LIMIT - ADDED_TAGS
+    }
+
+    fn result_limit(&self) -> usize {
+        PAGE_LIMIT
+    }
+
+    fn search<'a>(
+        &'a self,
+        nsfw_mode: NsfwMode,
+        tags: &'a [String],
+    ) -> BoxStream<'a, Result<Post, Error>> {
+        let mut tags = tags.to_vec();
+        tags.push("sort:random".to_string());
+        if let (NsfwMode::SFW, Some(sfw_tag)) = (nsfw_mode, &self.sfw_tag) {
+            tags.push(sfw_tag.clone());
+        }
+
+        stream::once(async move {
+            let posts = self.random_posts(&tags).await?;
+            Ok(stream::iter(posts.into_iter().map(Ok::<Post, Error>)))
+        })
+        .try_flatten()
+        .boxed()
+    }
+}
+
+fn convert_post(post: GelbooruPost, base_url: &str) -> Post {
+    let file_ext = post
+        .image
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .unwrap_or_default();
+    let score = post.score.unwrap_or_default();
+
+    Post {
+        id: post.id,
+        url: Some(format!(
+            "{}/index.php?page=post&s=view&id={}",
+            base_url, post.id
+        )),
+        title: None,
+        file_url: post.file_url,
+        file_path: None,
+        file_data: None,
+        file_ext,
+        md5: post.md5,
+        sample_url: post.sample_url,
+        preview_url: post.preview_url,
+        rating: Rating::from_booru(&post.rating),
+        tags: PostTags {
+            general: split_tag_string(&post.tags),
+            ..Default::default()
+        },
+        // only the total is known
+        score: PostScore {
+            up: score.max(0),
+            down: score.min(0),
+            total: score,
+        },
+        fav_count: 0,
+        description: String::new(),
+        sources: split_tag_string(&post.source),
+        created_at: None,
+    }
+}
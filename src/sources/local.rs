@@ -0,0 +1,182 @@
+//! Curated images stored on disk, each with an optional json file describing it
+
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use poise::serenity_prelude::Timestamp;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    configuration::NsfwMode,
+    filter::matches_query,
+    sources::{Post, PostScore, PostSource, PostTags, Rating},
+    Error,
+};
+
+/// File extensions which are picked up as images
+static IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "webm", "mp4"];
+
+/// Contents of the json file next to an image, named like the image but ending in `.json`.
+/// Every field is optional.
+#[derive(Debug, Default, Deserialize)]
+struct Sidecar {
+    artist: Option<String>,
+    title: Option<String>,
+    /// where the image has been published
+    source: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// `safe`, `questionable` or `explicit`. Defaults to `safe`
+    rating: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalSource {
+    /// the folder containing the images, if one has been configured
+    directory: Option<PathBuf>,
+}
+
+impl LocalSource {
+    pub fn new(directory: Option<PathBuf>) -> Self {
+        Self { directory }
+    }
+
+    /// Reads all images in the folder together with their json files.
+    ///
+    /// The folder is read again for every search, so images can be added
+    /// and removed while the bot is running.
+    async fn posts(&self) -> Result<Vec<Post>, Error> {
+        let directory = self.directory.as_ref().ok_or(Error::NoLocalDirectory)?;
+
+        let mut posts = Vec::new();
+        let mut entries = tokio::fs::read_dir(directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let is_image = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                .unwrap_or_default();
+            if is_image {
+                posts.extend(read_post(path).await?);
+            }
+        }
+
+        Ok(posts)
+    }
+}
+
+impl PostSource for LocalSource {
+    fn tag_limit(&self) -> usize {
+        // the whole query is checked by the bot itself
+        usize::MAX
+    }
+
+    fn result_limit(&self) -> usize {
+        usize::MAX
+    }
+
+    fn search<'a>(
+        &'a self,
+        _nsfw_mode: NsfwMode,
+        tags: &'a [String],
+    ) -> BoxStream<'a, Result<Post, Error>> {
+        stream::once(async move {
+            let mut posts: Vec<Post> = self
+                .posts()
+                .await?
+                .into_iter()
+                .filter(|post| matches_query(post, tags))
+                .collect();
+            posts.shuffle(&mut rand::thread_rng());
+            Ok(stream::iter(posts.into_iter().map(Ok::<Post, Error>)))
+        })
+        .try_flatten()
+        .boxed()
+    }
+}
+
+/// Creates a post from an image and its json file.
+///
+/// Images which can't be shown in embeds are skipped. A broken json file
+/// is treated like a missing one, so it doesn't break every search.
+async fn read_post(path: PathBuf) -> Result<Option<Post>, Error> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string();
+    if file_name.contains(' ') {
+        warn!(
+            "Skipping {}, file names with spaces can't be shown in embeds",
+            file_name
+        );
+        return Ok(None);
+    }
+
+    let sidecar_path = path.with_extension("json");
+    let sidecar = match tokio::fs::read(&sidecar_path).await {
+        Ok(sidecar) => serde_json::from_slice(&sidecar).unwrap_or_else(|err| {
+            warn!("Ignoring {}: {}", sidecar_path.display(), err);
+            Sidecar::default()
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Sidecar::default(),
+        Err(err) => return Err(err.into()),
+    };
+
+    let created_at = tokio::fs::metadata(&path)
+        .await?
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .and_then(|age| Timestamp::from_unix_timestamp(age.as_secs() as i64).ok());
+
+    Ok(Some(Post {
+        id: file_id(&file_name),
+        url: sidecar.source.clone(),
+        title: sidecar.title,
+        file_url: None,
+        file_ext: extension(&path),
+        file_path: Some(path),
+        file_data: None,
+        md5: None,
+        sample_url: None,
+        preview_url: None,
+        rating: sidecar
+            .rating
+            .map(|rating| Rating::from_booru(&rating))
+            .unwrap_or(Rating::Safe),
+        tags: PostTags {
+            general: sidecar.tags.iter().map(|tag| tag.to_lowercase()).collect(),
+            artist: sidecar.artist.into_iter().collect(),
+            ..Default::default()
+        },
+        score: PostScore::default(),
+        fav_count: 0,
+        description: String::new(),
+        sources: sidecar.source.into_iter().collect(),
+        created_at,
+    }))
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// A stable id for an image, derived from its file name (FNV-1a)
+fn file_id(file_name: &str) -> u64 {
+    file_name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,254 @@
+//! Sites posts can be fetched from, behind a common interface
+
+use std::{collections::HashSet, fmt::Debug, path::PathBuf, sync::Arc};
+
+use futures::{future, stream::BoxStream, StreamExt, TryStreamExt};
+use poise::serenity_prelude::Timestamp;
+
+use crate::{
+    configuration::{MediaMode, NsfwMode, SourceKind},
+    constants::POST_SEARCH_LIMIT,
+    filter::{allows_rating, split_query},
+    Error,
+};
+
+use self::{
+    danbooru::DanbooruSource, e621::E621Source, gelbooru::GelbooruSource, local::LocalSource,
+};
+
+pub mod danbooru;
+pub mod e621;
+pub mod gelbooru;
+pub mod local;
+
+/// One client for every site posts can be fetched from
+#[derive(Debug)]
+pub struct Sources {
+    pub(crate) e621: E621Source,
+    pub(crate) danbooru: DanbooruSource,
+    pub(crate) gelbooru: GelbooruSource,
+    pub(crate) safebooru: GelbooruSource,
+    pub(crate) local: LocalSource,
+}
+
+impl Sources {
+    pub fn get(&self, kind: SourceKind) -> &dyn PostSource {
+        match kind {
+            SourceKind::E621 => &self.e621,
+            SourceKind::Danbooru => &self.danbooru,
+            SourceKind::Gelbooru => &self.gelbooru,
+            SourceKind::Safebooru => &self.safebooru,
+            SourceKind::Local => &self.local,
+        }
+    }
+}
+
+/// A site which can be searched for posts
+pub trait PostSource: Debug + Send + Sync {
+    /// Maximum amount of tags a query passed to [PostSource::search] may contain
+    fn tag_limit(&self) -> usize;
+
+    /// Maximum amount of posts a single search can return
+    fn result_limit(&self) -> usize;
+
+    /// Searches for posts matching the tags, in random order.
+    ///
+    /// In sfw mode only the safe part of the site is searched, where the site allows it.
+    fn search<'a>(
+        &'a self,
+        nsfw_mode: NsfwMode,
+        tags: &'a [String],
+    ) -> BoxStream<'a, Result<Post, Error>>;
+}
+
+/// Searches a source for a random post which may be sent in the nsfw mode,
+/// skipping posts whose md5 is one of `known_hashes`.
+///
+/// Excluded tags which don't fit into the query are filtered after searching,
+/// as is the whole blacklist.
+///
+/// Posts without an md5 can't be checked here, they have to be hashed by the caller.
+pub async fn find_new_post(
+    source: &dyn PostSource,
+    nsfw_mode: NsfwMode,
+    media_mode: MediaMode,
+    tags: Vec<String>,
+    blacklist: &[String],
+    known_hashes: &HashSet<String>,
+) -> Result<Post, Error> {
+    let (tags, filter) = split_query(tags, blacklist, source.tag_limit());
+
+    let post = source
+        .search(nsfw_mode, &tags)
+        .take(POST_SEARCH_LIMIT)
+        .try_filter(|post| {
+            future::ready(
+                post.has_file()
+                    && allows_rating(nsfw_mode, post)
+                    && media_mode.allows(post.media_kind())
+                    && filter.allows(post)
+                    && !post
+                        .md5
+                        .as_ref()
+                        .map(|md5| known_hashes.contains(md5))
+                        .unwrap_or_default(),
+            )
+        })
+        .try_next()
+        .await?;
+
+    post.ok_or_else(|| Error::Uhhh("No posts this time...".to_string()))
+}
+
+/// A post of any of the sites, with the information the bot uses
+#[derive(Debug, Clone, Default)]
+pub struct Post {
+    /// id of the post on its site
+    pub id: u64,
+    /// link to the page of the post, if it has one
+    pub url: Option<String>,
+    /// title of the post. Only curated posts have one
+    pub title: Option<String>,
+    /// link to the full file. Some sites hide it for some posts
+    pub file_url: Option<String>,
+    /// the file on disk, for posts which are uploaded instead of linked
+    pub file_path: Option<PathBuf>,
+    /// the file behind `file_url`, if it has been downloaded already to hash it
+    pub file_data: Option<Arc<[u8]>>,
+    /// file extension, like `png` or `webm`
+    pub file_ext: String,
+    pub md5: Option<String>,
+    /// link to a downscaled version of the file
+    pub sample_url: Option<String>,
+    /// link to a thumbnail of the file
+    pub preview_url: Option<String>,
+    pub rating: Rating,
+    pub tags: PostTags,
+    pub score: PostScore,
+    pub fav_count: u64,
+    pub description: String,
+    pub sources: Vec<String>,
+    pub created_at: Option<Timestamp>,
+}
+
+impl Post {
+    /// False if the site hides the file, like e621 does for some posts when logged out
+    pub fn has_file(&self) -> bool {
+        self.file_url.is_some() || self.file_path.is_some()
+    }
+
+    pub fn media_kind(&self) -> MediaKind {
+        MediaKind::from_ext(&self.file_ext)
+    }
+}
+
+#[cfg(test)]
+impl Post {
+    /// A safe e621 post with a linked png, for the unit tests
+    pub(crate) fn example(id: u64) -> Self {
+        Self {
+            id,
+            url: Some(format!("https://e621.net/posts/{}", id)),
+            file_url: Some(format!("https://static1.e621.net/data/{}.png", id)),
+            file_ext: "png".to_string(),
+            rating: Rating::Safe,
+            ..Default::default()
+        }
+    }
+}
+
+/// What kind of file a post has, going by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Animated,
+    Video,
+    Flash,
+}
+
+impl MediaKind {
+    pub fn from_ext(ext: &str) -> Self {
+        match ext.to_lowercase().as_str() {
+            "gif" => Self::Animated,
+            "webm" | "mp4" | "mov" => Self::Video,
+            "swf" => Self::Flash,
+            _ => Self::Image,
+        }
+    }
+}
+
+/// Tags of a post by category. Sites without categories put every tag into `general`
+#[derive(Debug, Clone, Default)]
+pub struct PostTags {
+    pub general: Vec<String>,
+    pub species: Vec<String>,
+    pub character: Vec<String>,
+    pub copyright: Vec<String>,
+    pub artist: Vec<String>,
+    pub invalid: Vec<String>,
+    pub lore: Vec<String>,
+    pub meta: Vec<String>,
+}
+
+impl PostTags {
+    /// All tags, regardless of their category
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        [
+            &self.general,
+            &self.species,
+            &self.character,
+            &self.copyright,
+            &self.artist,
+            &self.invalid,
+            &self.lore,
+            &self.meta,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// Votes on a post. `down` is zero or negative
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PostScore {
+    pub up: i64,
+    pub down: i64,
+    pub total: i64,
+}
+
+/// Posts of unknown rating are treated as explicit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rating {
+    Safe,
+    Questionable,
+    #[default]
+    Explicit,
+}
+
+impl Rating {
+    /// The full name of a rating, as e621 accepts both `rating:s` and `rating:safe`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Safe => "safe",
+            Self::Questionable => "questionable",
+            Self::Explicit => "explicit",
+        }
+    }
+
+    /// Parses the ratings of the booru sites.
+    ///
+    /// Danbooru and Gelbooru split safe into `general` and `sensitive`,
+    /// only `general` is treated as safe. Unknown ratings are treated as explicit.
+    pub fn from_booru(rating: &str) -> Self {
+        match rating.to_lowercase().as_str() {
+            "g" | "general" | "safe" => Self::Safe,
+            "s" | "sensitive" | "q" | "questionable" => Self::Questionable,
+            _ => Self::Explicit,
+        }
+    }
+}
+
+/// Splits a space separated tag string, as the booru sites return them
+fn split_tag_string(tags: &str) -> Vec<String> {
+    tags.split_whitespace().map(|s| s.to_string()).collect()
+}
@@ -0,0 +1,314 @@
+//! Access to the e621 tag database, which is not covered by rs621
+
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::Deserialize;
+
+use crate::{
+    constants::{
+        AUTOCOMPLETE_CACHE_MINUTES, AUTOCOMPLETE_CACHE_SIZE, MAXIMUM_SEARCH_TAGS,
+        RESERVED_SEARCH_TAGS,
+    },
+    filter::can_filter,
+    http::HttpSettings,
+    query::{
+        edit_distance, lookup_names, tag_kind, tag_name, tag_prefix, validate_metatag, TagKind,
+    },
+    Error,
+};
+
+/// A tag as returned by the e621 api
+#[derive(Debug, Clone, Deserialize)]
+pub struct TagInfo {
+    pub name: String,
+    pub post_count: u64,
+    pub category: u8,
+}
+
+impl TagInfo {
+    /// e621 puts artist tags into category 1
+    pub fn is_artist(&self) -> bool {
+        self.category == 1
+    }
+}
+
+/// A tag from a query that does not exist in the tag database
+#[derive(Debug, Clone)]
+pub struct UnknownTag {
+    pub name: String,
+    /// An existing tag with a similar name
+    pub suggestion: Option<String>,
+}
+
+/// An alias from one tag to another, as returned by the e621 api
+#[derive(Debug, Clone, Deserialize)]
+pub struct TagAlias {
+    pub antecedent_name: String,
+    pub consequent_name: String,
+}
+
+/// The result of validating the tags of a query
+#[derive(Debug, Clone, Default)]
+pub struct TagValidation {
+    /// The tags with aliases resolved to their canonical names
+    pub tags: Vec<String>,
+    /// Problems which don't prevent the tags from being used
+    pub warnings: Vec<String>,
+    /// Problems which make the tags unusable
+    pub errors: Vec<String>,
+}
+
+impl TagValidation {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Response of `/tag_aliases.json`. Same quirk as [TagsResponse]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagAliasesResponse {
+    Aliases(Vec<TagAlias>),
+    Empty {},
+}
+
+/// Response of `/tags.json`. e621 returns an object instead of an empty list
+/// if nothing has been found.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagsResponse {
+    Tags(Vec<TagInfo>),
+    Empty {},
+}
+
+#[derive(Debug, Clone)]
+pub struct TagApi {
+    /// http client, shared between requests
+    http: reqwest::Client,
+    /// base url, for example `https://e621.net`
+    base_url: String,
+    /// login and api token
+    login: Option<(String, String)>,
+    /// recent autocomplete results by prefix, to keep autocompletion snappy
+    autocomplete_cache: DashMap<String, (Instant, Vec<TagInfo>)>,
+}
+
+impl TagApi {
+    pub fn new(base_url: &str, http: &HttpSettings) -> Result<Self, Error> {
+        let http = http.client()?;
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            login: None,
+            autocomplete_cache: DashMap::new(),
+        })
+    }
+
+    pub fn login(&mut self, login: String, token: String) {
+        self.login = Some((login, token));
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, Error> {
+        let mut request = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .query(query);
+        if let Some((login, token)) = &self.login {
+            request = request.basic_auth(login, Some(token));
+        }
+        Ok(request.send().await?.error_for_status()?.json().await?)
+    }
+
+    /// Looks up tags by their exact names. Tags that don't exist are missing in the result.
+    pub async fn tags(&self, names: &[&str]) -> Result<Vec<TagInfo>, Error> {
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let names = names.join(",");
+        let response: TagsResponse = self
+            .get("/tags.json", &[("search[name]", &names), ("limit", "320")])
+            .await?;
+
+        Ok(match response {
+            TagsResponse::Tags(tags) => tags,
+            TagsResponse::Empty {} => Vec::new(),
+        })
+    }
+
+    /// Gets the most popular tags starting with the given prefix.
+    ///
+    /// e621 needs at least 3 characters to autocomplete.
+    pub async fn autocomplete(&self, prefix: &str) -> Result<Vec<TagInfo>, Error> {
+        if prefix.chars().count() < 3 {
+            return Ok(Vec::new());
+        }
+
+        self.get(
+            "/tags/autocomplete.json",
+            &[("search[name_matches]", prefix), ("expiry", "7")],
+        )
+        .await
+    }
+
+    /// Same as [TagApi::autocomplete], but answers from a cache if possible
+    pub async fn autocomplete_cached(&self, prefix: &str) -> Result<Vec<TagInfo>, Error> {
+        let max_age = Duration::from_secs(AUTOCOMPLETE_CACHE_MINUTES * 60);
+
+        if let Some(entry) = self.autocomplete_cache.get(prefix) {
+            let (fetched_at, tags) = entry.value();
+            if fetched_at.elapsed() < max_age {
+                return Ok(tags.clone());
+            }
+        }
+
+        let tags = self.autocomplete(prefix).await?;
+
+        if self.autocomplete_cache.len() >= AUTOCOMPLETE_CACHE_SIZE {
+            self.autocomplete_cache
+                .retain(|_, (fetched_at, _)| fetched_at.elapsed() < max_age);
+        }
+        if self.autocomplete_cache.len() >= AUTOCOMPLETE_CACHE_SIZE {
+            self.autocomplete_cache.clear();
+        }
+        self.autocomplete_cache
+            .insert(prefix.to_string(), (Instant::now(), tags.clone()));
+
+        Ok(tags)
+    }
+
+    /// Finds all tags of a query which do not exist, together with
+    /// similarly named tags in case of typos.
+    pub async fn unknown_tags(&self, tags: &[String]) -> Result<Vec<UnknownTag>, Error> {
+        let names = lookup_names(tags);
+        let known = self.tags(&names).await?;
+
+        let mut unknown = Vec::new();
+        for name in names {
+            if known.iter().any(|tag| tag.name == name) {
+                continue;
+            }
+
+            // typos are more likely towards the end of a tag
+            let prefix_length = (name.chars().count() / 2).max(3);
+            let prefix: String = name.chars().take(prefix_length).collect();
+            let suggestion = self
+                .autocomplete(&prefix)
+                .await?
+                .into_iter()
+                .map(|tag| (edit_distance(name, &tag.name), tag.name))
+                .filter(|(distance, _)| *distance <= 3)
+                .min_by_key(|(distance, _)| *distance)
+                .map(|(_, name)| name);
+
+            unknown.push(UnknownTag {
+                name: name.to_string(),
+                suggestion,
+            });
+        }
+
+        Ok(unknown)
+    }
+
+    /// Looks up the active aliases for the given tag names
+    pub async fn aliases(&self, names: &[&str]) -> Result<Vec<TagAlias>, Error> {
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let names = names.join(",");
+        let response: TagAliasesResponse = self
+            .get(
+                "/tag_aliases.json",
+                &[
+                    ("search[antecedent_name]", &names),
+                    ("search[status]", "active"),
+                    ("limit", "320"),
+                ],
+            )
+            .await?;
+
+        Ok(match response {
+            TagAliasesResponse::Aliases(aliases) => aliases,
+            TagAliasesResponse::Empty {} => Vec::new(),
+        })
+    }
+
+    /// Validates the tags of a query before they are saved.
+    ///
+    /// Resolves aliases, checks metatags, looks for tags that don't exist and
+    /// makes sure the query stays below the tag limit of e621.
+    pub async fn validate(&self, tags: &[String]) -> Result<TagValidation, Error> {
+        let mut validation = TagValidation::default();
+
+        let tags: Vec<String> = tags
+            .iter()
+            .map(|tag| match tag_kind(tag) {
+                TagKind::Meta => tag.to_string(),
+                _ => tag.to_lowercase(),
+            })
+            .collect();
+
+        // excluded tags which don't fit into the query are filtered after searching
+        let limit = MAXIMUM_SEARCH_TAGS - RESERVED_SEARCH_TAGS;
+        let query_tags = tags.iter().filter(|tag| !can_filter(tag)).count();
+        if query_tags > limit {
+            validation.errors.push(format!(
+                "Too many tags: {} of at most {}, not counting excluded tags",
+                query_tags, limit
+            ));
+        }
+
+        for tag in tags.iter().filter(|tag| tag_kind(tag) == TagKind::Meta) {
+            if let Err(err) = validate_metatag(tag_name(tag)) {
+                validation.errors.push(err);
+            }
+        }
+
+        let aliases = self.aliases(&lookup_names(&tags)).await?;
+        validation.tags = tags
+            .iter()
+            .map(|tag| {
+                match aliases
+                    .iter()
+                    .find(|alias| alias.antecedent_name == tag_name(tag))
+                {
+                    Some(alias) => {
+                        validation.warnings.push(format!(
+                            "`{}` is an alias of `{}`",
+                            alias.antecedent_name, alias.consequent_name
+                        ));
+                        format!("{}{}", tag_prefix(tag), alias.consequent_name)
+                    }
+                    None => tag.to_string(),
+                }
+            })
+            .collect();
+
+        for unknown_tag in self.unknown_tags(&validation.tags).await? {
+            let mut message = format!("`{}` does not exist", unknown_tag.name);
+            if let Some(suggestion) = unknown_tag.suggestion {
+                message.push_str(&format!(", did you mean `{}`?", suggestion));
+            }
+
+            // a search for a tag that doesn't exist never finds anything,
+            // excluding it or making it optional does no harm though
+            let required = validation
+                .tags
+                .iter()
+                .any(|tag| tag_kind(tag) == TagKind::Required && tag == &unknown_tag.name);
+            if required {
+                validation.errors.push(message);
+            } else {
+                validation.warnings.push(message);
+            }
+        }
+
+        Ok(validation)
+    }
+}
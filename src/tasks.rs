@@ -1,22 +1,91 @@
 use std::{
     collections::{HashMap, HashSet},
+    future::Future,
+    sync::{atomic::AtomicUsize, Arc},
     time::Duration,
 };
 
 use crate::{
-    configuration::TimeoutMode,
-    constants::MINIMUM_TIMEOUT_MINUTES,
-    utils::{embed_from_post, post_buttons},
+    configuration::{EmbedLayout, PostFormat, TimeoutMode},
+    constants::{
+        BAD_POST_ATTEMPTS, DELETE_VOTES_NEEDED, GALLERY_CACHE_SIZE, MAXIMUM_TIMEOUT_MINUTES,
+        MINIMUM_TIMEOUT_MINUTES,
+    },
+    error::ErrorKind,
+    messenger::Messenger,
+    sources::Post,
+    utils::{message_from_post, MessageStyle, PostMessage, ReadAhead},
     Data, Error,
 };
 
+use dashmap::DashMap;
 use futures::stream::StreamExt;
 use poise::serenity_prelude::{
-    ChannelId, ComponentInteractionCollectorBuilder, Context, GuildId, InteractionResponseType,
-    MessageId, UserId,
+    ChannelId, ComponentInteractionCollectorBuilder, Context, GuildId, MessageId, Timestamp, UserId,
 };
 use rand::Rng;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// Runtime state of the posting loop of a single channel
+#[derive(Debug, Clone, Default)]
+pub struct TaskState {
+    /// When the loop is going to post next
+    pub(crate) next_post_at: Option<Timestamp>,
+    /// When the loop posted last
+    pub(crate) last_post_at: Option<Timestamp>,
+    /// The id of the last post on its site
+    pub(crate) last_post_id: Option<u64>,
+    /// The last error that occured inside the loop
+    pub(crate) last_error: Option<String>,
+    /// How many posts have been sent for each tag set
+    pub(crate) tag_set_counts: HashMap<String, u64>,
+}
+
+/// A post which has been sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Published {
+    pub id: u64,
+    /// the hash of its file, if the site told it or it has been hashed
+    pub md5: Option<String>,
+}
+
+/// What happened when the loop tried to post
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostOutcome {
+    /// The posts have been sent, in order
+    Posted(Vec<Published>),
+    /// Nothing has been sent, because the search failed
+    Failed(ErrorKind, String),
+    /// None of the posts could be shown, the loop has to stop for this channel
+    Stop(String),
+}
+
+/// How the loop reacts to a failed search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Try again after this many minutes instead of the usual timeout
+    Backoff(u64),
+    /// Stop the channel until someone fixes the problem
+    Pause,
+    /// Try again after the usual timeout
+    Retry,
+}
+
+/// Decides how the loop reacts to the `failures`th failed search in a row
+pub fn recovery(kind: ErrorKind, failures: u32) -> Recovery {
+    match kind {
+        ErrorKind::Transient => {
+            let factor = 2_u64.saturating_pow(failures.saturating_sub(1));
+            Recovery::Backoff(
+                MINIMUM_TIMEOUT_MINUTES
+                    .saturating_mul(factor)
+                    .min(MAXIMUM_TIMEOUT_MINUTES),
+            )
+        }
+        ErrorKind::Auth | ErrorKind::BadQuery => Recovery::Pause,
+        ErrorKind::Other => Recovery::Retry,
+    }
+}
 
 /// Starts the loop for a channel in a guild
 pub async fn send_images_loop(
@@ -24,135 +93,1173 @@ pub async fn send_images_loop(
     guild: GuildId,
     channel: ChannelId,
     mut stop_signal: tokio::sync::watch::Receiver<bool>,
+    mut reset_signal: tokio::sync::watch::Receiver<()>,
 ) {
-    let discord_http = data.context().http.clone();
+    let messenger = data.messenger();
+    let galleries = data.galleries();
+    // failed searches in a row
+    let mut failures = 0;
 
-    loop {
-        match data.get_post(guild, channel).await {
-            Err(err) => {
-                match err {
-                    Error::Rs621(ref e) => match e {
-                        rs621::error::Error::Http { code, reason, .. } => {
-                            let reason =
-                                reason.to_owned().unwrap_or_else(|| "Unkown reason".into());
-                            let _ = channel
-                                .say(
-                                    &discord_http,
-                                    format!("API Error: Code {}, {}", code, reason),
-                                )
-                                .await;
-                        }
-                        _ => {
-                            let _ = channel.say(&discord_http, &err.to_string()).await;
-                        }
-                    },
-                    _ => {
-                        let _ = channel.say(&discord_http, &err.to_string()).await;
+    'posting: loop {
+        let mut retry_after = None;
+
+        let style = data.message_style(guild, channel).await;
+
+        match publish_next(&data, &messenger, &galleries, guild, channel, &style).await {
+            PostOutcome::Posted(published) => {
+                failures = 0;
+                let last_post_id = published.last().map(|post| post.id);
+                for md5 in published.into_iter().filter_map(|post| post.md5) {
+                    data.remember_post(channel, md5).await;
+                }
+                data.update_task_state(channel, |state| {
+                    state.last_post_at = Some(Timestamp::now());
+                    state.last_post_id = last_post_id;
+                    state.last_error = None;
+                });
+            }
+            PostOutcome::Failed(kind, err) => {
+                failures += 1;
+                data.update_task_state(channel, |state| state.last_error = Some(err.clone()));
+                let log_channel = data.log_channel(guild).await;
+
+                match recovery(kind, failures) {
+                    Recovery::Backoff(minutes) => {
+                        let message = format!("{}. Trying again in {} minutes", err, minutes);
+                        report(&messenger, log_channel, channel, &message, false).await;
+                        retry_after = Some(minutes);
+                    }
+                    Recovery::Pause => {
+                        let message = format!(
+                            "{}. Paused this channel, use /start once the problem has been fixed",
+                            err
+                        );
+                        report(&messenger, log_channel, channel, &message, true).await;
+                        // marks the channel as inactive, so it isn't restarted with the bot
+                        data.stop(guild, channel).await;
+                        break 'posting;
+                    }
+                    Recovery::Retry => {
+                        report(&messenger, log_channel, channel, &err, true).await;
                     }
                 }
-                error!("{}", err);
             }
-            Ok(post) => {
-                info!(
-                    "Posting {:?} in guild {} in channel {}",
-                    post.id, guild, channel
+            PostOutcome::Stop(err) => {
+                data.update_task_state(channel, |state| state.last_error = Some(err.clone()));
+                let message = format!(
+                    "None of the last {} posts could be shown. {}. Stopped this channel, use /start to try again",
+                    BAD_POST_ATTEMPTS, err
                 );
-                let embed = match embed_from_post(&post) {
-                    Ok(embed) => embed,
-                    Err(err) => {
-                        let error_message = format!("Error: {}. Stopping for this channel", err);
-                        error!("{}", &error_message);
-                        let _ = channel.say(&discord_http, error_message).await;
-                        break;
+                let log_channel = data.log_channel(guild).await;
+                report(&messenger, log_channel, channel, &message, true).await;
+                data.stop(guild, channel).await;
+                break 'posting;
+            }
+        }
+
+        // the timer starts over every time it gets reset
+        loop {
+            let sleep_duration = match retry_after.take() {
+                Some(minutes) => minutes,
+                None => next_sleep_duration(&data, guild, channel).await,
+            };
+
+            info!("Waiting for {} minutes for the next post", sleep_duration);
+            data.update_task_state(channel, |state| {
+                state.next_post_at = Timestamp::from_unix_timestamp(
+                    Timestamp::now().unix_timestamp() + sleep_duration as i64 * 60,
+                )
+                .ok();
+            });
+
+            let sleep_task = tokio::time::sleep(Duration::from_secs(sleep_duration * 60));
+
+            tokio::select! {
+                _ = sleep_task => { break },
+                Ok(()) = reset_signal.changed() => {
+                    info!("Resetting timer for channel {}", channel);
+                },
+                _ = stop_signal.changed() => { break 'posting },
+            };
+        }
+    }
+
+    data.update_task_state(channel, |state| state.next_post_at = None);
+}
+
+/// Sends posts found by the tags of the channel, or one of its tag sets.
+///
+/// The tag set is picked once for the whole message, and counted once its posts have been sent.
+async fn publish_search<M: Messenger>(
+    data: &Data,
+    messenger: &M,
+    galleries: &Galleries,
+    guild: GuildId,
+    channel: ChannelId,
+    style: &MessageStyle,
+) -> PostOutcome {
+    let (tag_set, tags) = match data.pick_tags(guild, channel).await {
+        Ok(picked) => picked,
+        Err(err) => return PostOutcome::Failed(err.kind(), err.to_string()),
+    };
+
+    let outcome = publish(messenger, galleries, guild, channel, style, || {
+        data.get_post_with_tags(guild, channel, tags.clone())
+    })
+    .await;
+
+    if let (Some(tag_set), PostOutcome::Posted(published)) = (tag_set, &outcome) {
+        data.update_task_state(channel, |state| {
+            *state.tag_set_counts.entry(tag_set).or_default() += published.len() as u64;
+        });
+    }
+    outcome
+}
+
+/// Sends the next pages of the pool the channel is reading, or posts found by its tags otherwise.
+///
+/// Once all pages of a pool have been posted, the channel moves on to another random pool
+/// if it picked the pool randomly, or goes back to searching for posts.
+async fn publish_next<M: Messenger>(
+    data: &Data,
+    messenger: &M,
+    galleries: &Galleries,
+    guild: GuildId,
+    channel: ChannelId,
+    style: &MessageStyle,
+) -> PostOutcome {
+    let progress = match data.pool_progress(guild, channel).await {
+        Some(progress) => progress,
+        None => return publish_search(data, messenger, galleries, guild, channel, style).await,
+    };
+
+    let mut pool = match data.pool(guild, channel, progress.pool_id).await {
+        Ok(pool) => pool,
+        Err(err) => return PostOutcome::Failed(err.kind(), err.to_string()),
+    };
+    let mut page = progress.page;
+
+    if page >= pool.post_ids.len() {
+        let next_pool = match data.finish_pool(guild, channel).await {
+            Ok(next_pool) => next_pool,
+            Err(err) => return PostOutcome::Failed(err.kind(), err.to_string()),
+        };
+        let message = match &next_pool {
+            Some(next_pool) => format!(
+                "That was the last page of {}. Next up: {}",
+                pool.title(),
+                next_pool.title()
+            ),
+            None => format!("That was the last page of {}.", pool.title()),
+        };
+        if let Err(err) = messenger.say(channel, &message).await {
+            error!("Could not announce the end of a pool: {}", err);
+        }
+
+        match next_pool {
+            Some(next_pool) => {
+                pool = next_pool;
+                page = 0;
+            }
+            None => return publish_search(data, messenger, galleries, guild, channel, style).await,
+        }
+    }
+
+    let style = MessageStyle {
+        pool: Some(pool.id),
+        ..style.clone()
+    };
+    let next_page = AtomicUsize::new(page);
+    let outcome = publish(messenger, galleries, guild, channel, &style, || {
+        data.pool_page(guild, channel, &pool, &next_page)
+    })
+    .await;
+
+    // pages which failed to be fetched or sent are tried again, unless the pool has run out
+    let next_page = next_page.into_inner();
+    if !matches!(outcome, PostOutcome::Failed(..)) || next_page >= pool.post_ids.len() {
+        data.set_pool_page(guild, channel, pool.id, next_page).await;
+    }
+    outcome
+}
+
+/// Reports an error of the loop of `channel` in the log channel of its guild.
+///
+/// Without a log channel, the error is only sent to `channel` itself if `fallback` is set.
+async fn report<M: Messenger>(
+    messenger: &M,
+    log_channel: Option<ChannelId>,
+    channel: ChannelId,
+    message: &str,
+    fallback: bool,
+) {
+    error!("Error in channel {}: {}", channel, message);
+    let result = match log_channel {
+        Some(log_channel) => {
+            messenger
+                .say(log_channel, &format!("<#{}>: {}", channel, message))
+                .await
+        }
+        None if fallback => messenger.say(channel, message).await,
+        None => Ok(()),
+    };
+    if let Err(err) = result {
+        error!("Could not report error: {}", err);
+    }
+}
+
+/// Calculates how many minutes to wait for the next post
+async fn next_sleep_duration(data: &Data, guild: GuildId, channel: ChannelId) -> u64 {
+    let timeout_minutes = data.timeout(guild, channel).await.unwrap_or(40);
+
+    match data
+        .timeout_mode(guild, channel)
+        .await
+        .unwrap_or(TimeoutMode::Normal)
+    {
+        TimeoutMode::Random => {
+            let lower_limit = MINIMUM_TIMEOUT_MINUTES;
+            let upper_limit = timeout_minutes;
+
+            let mut rng = rand::thread_rng();
+            rng.gen_range(lower_limit..=upper_limit)
+        }
+        TimeoutMode::Normal => timeout_minutes,
+    }
+}
+
+/// Sends as many posts from `next_post` as the format of the channel asks for,
+/// or returns why there are none.
+///
+/// Posts which can't be shown are skipped. Only after [BAD_POST_ATTEMPTS] of them
+/// in a row the loop has to stop. Galleries and pages are sent with fewer posts
+/// if no more can be found.
+pub async fn publish<M, F, Fut>(
+    messenger: &M,
+    galleries: &Galleries,
+    guild: GuildId,
+    channel: ChannelId,
+    style: &MessageStyle,
+    mut next_post: F,
+) -> PostOutcome
+where
+    M: Messenger,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Post, Error>>,
+{
+    let wanted = style.posts_per_message();
+    let mut found: Vec<(Post, PostMessage)> = Vec::with_capacity(wanted);
+    // posts which can't be shown in a row
+    let mut bad_posts = 0;
+    let mut last_error = String::new();
+
+    while found.len() < wanted {
+        let post = match next_post().await {
+            Ok(post) => post,
+            Err(err) if found.is_empty() => {
+                let message = match &err {
+                    Error::Rs621(rs621::error::Error::Http { code, reason, .. }) => {
+                        let reason = reason.to_owned().unwrap_or_else(|| "Unkown reason".into());
+                        format!("API Error: Code {}, {}", code, reason)
                     }
+                    _ => err.to_string(),
                 };
-                let ctx = data.context().clone();
-                tokio::spawn(async move {
-                    if let Err(err) = channel
-                        .send_message(&ctx, |m| {
-                            m.set_embed(embed.clone())
-                                .components(|c| c.add_action_row(post_buttons(0, 4)))
-                        })
-                        .await
-                    {
-                        error!("{}", err);
-                    };
-                });
+                return PostOutcome::Failed(err.kind(), message);
+            }
+            Err(err) => {
+                warn!(
+                    "Sending {} of {} posts in channel {}: {}",
+                    found.len(),
+                    wanted,
+                    channel,
+                    err
+                );
+                break;
+            }
+        };
+
+        // searches with few results run out of posts before the gallery is full
+        if found.iter().any(|(other, _)| is_same_post(other, &post)) {
+            break;
+        }
+
+        match message_from_post(&post, style) {
+            Ok(message) => {
+                bad_posts = 0;
+                found.push((post, message));
+            }
+            Err(err) => {
+                bad_posts += 1;
+                warn!(
+                    "Skipping #{} in channel {} ({}/{}): {}",
+                    post.id, channel, bad_posts, BAD_POST_ATTEMPTS, err
+                );
+                last_error = format!("Could not show #{}: {}", post.id, err);
+                if bad_posts >= BAD_POST_ATTEMPTS {
+                    break;
+                }
             }
         }
+    }
+
+    if found.is_empty() {
+        return PostOutcome::Stop(last_error);
+    }
 
-        let timeout_minutes = data.timeout(guild, channel).await.unwrap_or(40);
+    let ids: Vec<u64> = found.iter().map(|(post, _)| post.id).collect();
+    info!(
+        "Posting {:?} in guild {} in channel {}",
+        ids, guild, channel
+    );
 
-        let sleep_duration = match data
-            .timeout_mode(guild, channel)
+    // files can't be swapped when flipping pages, posts with them are sent as a gallery instead
+    let paged = style.format == PostFormat::Pages
+        && found
+            .iter()
+            .all(|(_, message)| message.attachment.is_none());
+    let sent = if found.len() == 1 {
+        messenger.send_post(channel, found[0].1.clone()).await
+    } else if paged {
+        let total = found.len();
+        let pages: Vec<PostMessage> = found
+            .iter()
+            .enumerate()
+            .map(|(index, (_, message))| page(message.clone(), index, total))
+            .collect();
+        let mut first_page = pages[0].clone();
+        // reading ahead continues after the last page
+        first_page.read_ahead = pages[total - 1].read_ahead;
+        messenger
+            .send_pages(channel, first_page)
             .await
-            .unwrap_or(TimeoutMode::Normal)
-        {
-            TimeoutMode::Random => {
-                let lower_limit = MINIMUM_TIMEOUT_MINUTES;
-                let upper_limit = timeout_minutes;
+            .map(|message| {
+                galleries.insert(message, pages);
+                message
+            })
+    } else {
+        // discord limits the text of all embeds of a message together, so they are kept short
+        let gallery_style = MessageStyle {
+            layout: EmbedLayout::Minimal,
+            ..style.clone()
+        };
+        // every post has been shown once already, so none is left out of the gallery
+        let messages = found
+            .iter()
+            .map(|(post, message)| {
+                message_from_post(post, &gallery_style).unwrap_or_else(|_| message.clone())
+            })
+            .collect();
+        messenger.send_gallery(channel, messages).await
+    };
 
-                let mut rng = rand::thread_rng();
-                rng.gen_range(lower_limit..=upper_limit)
+    match sent {
+        Ok(_) => PostOutcome::Posted(
+            found
+                .into_iter()
+                .map(|(post, _)| Published {
+                    id: post.id,
+                    md5: post.md5,
+                })
+                .collect(),
+        ),
+        Err(err) => {
+            error!("{}", err);
+            PostOutcome::Failed(err.kind(), err.to_string())
+        }
+    }
+}
+
+/// If two posts are the same, or at least have the same file
+fn is_same_post(post: &Post, other: &Post) -> bool {
+    post.id == other.id || (post.md5.is_some() && post.md5 == other.md5)
+}
+
+/// Labels the message of a post with its page number
+fn page(mut message: PostMessage, index: usize, total: usize) -> PostMessage {
+    let label = format!("Page {}/{}", index + 1, total);
+    message.content = Some(match message.content {
+        Some(content) => format!("{}\n{}", label, content),
+        None => label,
+    });
+    message
+}
+
+/// The pages of the messages which can be flipped through, by message
+#[derive(Debug, Clone, Default)]
+pub struct Galleries {
+    galleries: Arc<DashMap<MessageId, Pages>>,
+}
+
+/// The pages of a message, and which of them is shown
+#[derive(Debug)]
+struct Pages {
+    pages: Vec<PostMessage>,
+    current: usize,
+}
+
+impl Galleries {
+    /// Remembers the pages of a message. The oldest messages are forgotten once there are too many
+    pub fn insert(&self, message: MessageId, pages: Vec<PostMessage>) {
+        self.galleries.insert(message, Pages { pages, current: 0 });
+
+        if self.galleries.len() > GALLERY_CACHE_SIZE {
+            // message ids grow with the time they have been sent at
+            let oldest = self.galleries.iter().map(|entry| *entry.key()).min();
+            if let Some(oldest) = oldest {
+                self.galleries.remove(&oldest);
             }
-            TimeoutMode::Normal => timeout_minutes,
+        }
+    }
+
+    /// Turns to the next or previous page of a message, wrapping around at the ends.
+    ///
+    /// Returns the page to show, if the pages of the message are known
+    pub fn flip(&self, message: MessageId, forward: bool) -> Option<PostMessage> {
+        let mut gallery = self.galleries.get_mut(&message)?;
+        let total = gallery.pages.len();
+        gallery.current = if forward {
+            (gallery.current + 1) % total
+        } else {
+            (gallery.current + total - 1) % total
         };
+        Some(gallery.pages[gallery.current].clone())
+    }
+}
 
-        info!("Waiting for {} minutes for the next post", sleep_duration);
+/// Shows the next or previous page of the message whose button has been clicked
+pub async fn handle_page_flip<M: Messenger>(
+    messenger: &M,
+    galleries: &Galleries,
+    interaction: &M::Interaction,
+    message: MessageId,
+    forward: bool,
+) {
+    let result = match galleries.flip(message, forward) {
+        Some(page) => messenger.show_page(interaction, page).await,
+        // the pages of old messages and of messages sent before a restart are gone
+        None => messenger.acknowledge(interaction).await,
+    };
+    if let Err(err) = result {
+        error!("Error flipping page: {}", err);
+    }
+}
 
-        let sleep_task = tokio::time::sleep(Duration::from_secs(sleep_duration * 60));
+/// Who voted to delete which message
+#[derive(Debug, Default)]
+pub struct DeleteVotes {
+    voters: HashMap<MessageId, HashSet<UserId>>,
+}
 
-        tokio::select! {
-            _ = sleep_task => {},
-            _ = stop_signal.changed() => { break },
-        };
+/// Counts a click on the delete button, and deletes the message once enough users clicked it
+pub async fn handle_delete_vote<M: Messenger>(
+    messenger: &M,
+    votes: &mut DeleteVotes,
+    interaction: &M::Interaction,
+    channel: ChannelId,
+    message: MessageId,
+    user: UserId,
+) {
+    let voters = votes.voters.entry(message).or_default();
+    if voters.insert(user) {
+        if let Err(err) = messenger
+            .update_delete_button(interaction, voters.len(), DELETE_VOTES_NEEDED)
+            .await
+        {
+            error!("Error updating original interaction response: {}", err);
+        }
+    } else if let Err(err) = messenger.acknowledge(interaction).await {
+        error!("Error acknowledging interaction: {}", err);
+    }
+
+    if voters.len() >= DELETE_VOTES_NEEDED {
+        if let Err(err) = messenger.delete_message(channel, message).await {
+            error!("Error deleting original interaction response: {}", err)
+        } else {
+            info!("Deleted message in {}", channel);
+        }
+        votes.voters.remove(&message);
+    }
+}
+
+/// Shows the page of a pool after the one whose button has been clicked,
+/// only to the user who clicked it.
+///
+/// The click is deferred first, as finding the page can take longer than discord waits for an answer.
+pub async fn handle_read_ahead<M: Messenger>(
+    data: &Data,
+    messenger: &M,
+    interaction: &M::Interaction,
+    guild: GuildId,
+    channel: ChannelId,
+    read_ahead: ReadAhead,
+) {
+    if let Err(err) = messenger.defer_privately(interaction).await {
+        error!("Error deferring the next page: {}", err);
+        return;
+    }
+
+    let style = MessageStyle {
+        layout: data.embed_layout(guild, channel).await.unwrap_or_default(),
+        pool: Some(read_ahead.pool_id),
+        ..Default::default()
+    };
+
+    let result = match data.page_after(guild, channel, read_ahead).await {
+        Ok(Some(post)) => match message_from_post(&post, &style) {
+            Ok(page) => messenger.show_private_page(interaction, page).await,
+            Err(err) => {
+                let content = format!("Could not show #{}: {}", post.id, err);
+                messenger.reply_privately(interaction, &content).await
+            }
+        },
+        Ok(None) => {
+            messenger
+                .reply_privately(interaction, "That was the last page of the pool.")
+                .await
+        }
+        Err(err) => {
+            error!(
+                "Could not read ahead in pool #{}: {}",
+                read_ahead.pool_id, err
+            );
+            let content = format!("Could not get the next page: {}", err);
+            messenger.reply_privately(interaction, &content).await
+        }
+    };
+    if let Err(err) = result {
+        error!("Error showing the next page: {}", err);
     }
 }
 
-/// listens for delete button clicks on image posts
-pub async fn delete_button_listener(ctx: Context) {
+/// listens for delete, page and read ahead button clicks on image posts
+pub async fn button_listener(ctx: Context, data: Data) {
+    let messenger = data.messenger();
+    let galleries = data.galleries();
+
     let mut collector = ComponentInteractionCollectorBuilder::new(&ctx)
-        .filter(|interaction| interaction.data.custom_id == "delete-post")
+        .filter(|interaction| {
+            let custom_id = interaction.data.custom_id.as_str();
+            matches!(custom_id, "delete-post" | "previous-page" | "next-page")
+                || ReadAhead::from_custom_id(custom_id).is_some()
+        })
         .build();
 
-    let mut authors = HashMap::<MessageId, HashSet<UserId>>::new();
+    let mut votes = DeleteVotes::default();
     while let Some(interaction) = collector.next().await {
-        let authors_of_message = authors.entry(interaction.message.id).or_default();
-        if authors_of_message.insert(interaction.user.id) {
-            if let Err(err) = interaction
-                .create_interaction_response(&ctx.http, |resp| {
-                    resp.kind(InteractionResponseType::UpdateMessage)
-                        .interaction_response_data(|resp_data| {
-                            resp_data.components(|c| {
-                                c.set_action_rows(vec![post_buttons(authors_of_message.len(), 4)])
-                            })
-                        })
-                })
+        match interaction.data.custom_id.as_str() {
+            "delete-post" => {
+                handle_delete_vote(
+                    &messenger,
+                    &mut votes,
+                    interaction.as_ref(),
+                    interaction.channel_id,
+                    interaction.message.id,
+                    interaction.user.id,
+                )
                 .await
-            {
-                error!("Error updating original interaction response: {}", err);
             }
-        } else if let Err(err) = interaction
-            .create_interaction_response(&ctx.http, |resp| {
-                resp.kind(InteractionResponseType::DeferredUpdateMessage)
-                    .interaction_response_data(|resp_data| resp_data)
-            })
-            .await
-        {
-            error!("Error acknowledging interaction: {}", err);
+            "previous-page" | "next-page" => {
+                handle_page_flip(
+                    &messenger,
+                    &galleries,
+                    interaction.as_ref(),
+                    interaction.message.id,
+                    interaction.data.custom_id == "next-page",
+                )
+                .await
+            }
+            custom_id => {
+                let (guild, read_ahead) =
+                    match (interaction.guild_id, ReadAhead::from_custom_id(custom_id)) {
+                        (Some(guild), Some(read_ahead)) => (guild, read_ahead),
+                        _ => continue,
+                    };
+                // searching for the page takes a while, the other buttons keep working meanwhile
+                let data = data.clone();
+                let messenger = messenger.clone();
+                tokio::spawn(async move {
+                    handle_read_ahead(
+                        &data,
+                        &messenger,
+                        interaction.as_ref(),
+                        guild,
+                        interaction.channel_id,
+                        read_ahead,
+                    )
+                    .await
+                });
+            }
         }
+    }
+}
 
-        if authors_of_message.len() >= 4 {
-            if let Err(err) = ctx
-                .http
-                .delete_message(interaction.channel_id.0, interaction.message.id.0)
-                .await
-            {
-                error!("Error deleting original interaction response: {}", err)
-            } else {
-                info!("Deleted message in {}", interaction.channel_id);
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use futures::future;
+
+    use super::*;
+    use crate::{
+        messenger::recording::{RecordingMessenger, Sent},
+        utils::{Attachment, AttachmentSource, Oversized},
+    };
+
+    const GUILD: GuildId = GuildId(1);
+    const CHANNEL: ChannelId = ChannelId(2);
+
+    #[tokio::test]
+    async fn publish_sends_post() {
+        let messenger = RecordingMessenger::default();
+
+        let outcome = publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &MessageStyle::default(),
+            || future::ready(Ok(Post::example(5))),
+        )
+        .await;
+
+        assert_eq!(
+            outcome,
+            PostOutcome::Posted(vec![Published { id: 5, md5: None }])
+        );
+        assert_eq!(
+            messenger.sent(),
+            vec![Sent::Post {
+                channel: CHANNEL,
+                content: None,
+                attachment: None
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_uploads_local_files() {
+        let messenger = RecordingMessenger::default();
+        let mut post = Post::example(5);
+        post.file_path = Some(PathBuf::from("/images/pikachu.png"));
+
+        publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &MessageStyle::default(),
+            || future::ready(Ok(post.clone())),
+        )
+        .await;
+
+        assert_eq!(
+            messenger.sent(),
+            vec![Sent::Post {
+                channel: CHANNEL,
+                content: None,
+                attachment: Some(Attachment {
+                    source: AttachmentSource::Path(PathBuf::from("/images/pikachu.png")),
+                    file_name: "pikachu.png".to_string(),
+                    oversized: Oversized::Fail,
+                })
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_spoilers_posts_with_spoiler_tags() {
+        let messenger = RecordingMessenger::default();
+        let mut post = Post::example(5);
+        post.tags.general = vec!["blood".to_string(), "solo".to_string()];
+        post.sample_url = Some("https://static1.e621.net/data/sample/5.jpg".to_string());
+        let style = MessageStyle {
+            spoiler_tags: vec!["blood".to_string(), "gore".to_string()],
+            ..Default::default()
+        };
+
+        publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &style,
+            || future::ready(Ok(post.clone())),
+        )
+        .await;
+
+        assert_eq!(
+            messenger.sent(),
+            vec![Sent::Post {
+                channel: CHANNEL,
+                content: Some("⚠️ Content warning: `blood`".to_string()),
+                attachment: Some(Attachment {
+                    source: AttachmentSource::Url(
+                        "https://static1.e621.net/data/sample/5.jpg".to_string()
+                    ),
+                    file_name: "SPOILER_5.jpg".to_string(),
+                    oversized: Oversized::Link("https://static1.e621.net/data/5.png".to_string()),
+                })
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_spoilers_local_files() {
+        let messenger = RecordingMessenger::default();
+        let mut post = Post::example(5);
+        post.file_path = Some(PathBuf::from("/images/pikachu.png"));
+        post.tags.general = vec!["blood_on_face".to_string()];
+        let style = MessageStyle {
+            spoiler_tags: vec!["blood*".to_string()],
+            ..Default::default()
+        };
+
+        publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &style,
+            || future::ready(Ok(post.clone())),
+        )
+        .await;
+
+        assert_eq!(
+            messenger.sent(),
+            vec![Sent::Post {
+                channel: CHANNEL,
+                content: Some("⚠️ Content warning: `blood*`".to_string()),
+                attachment: Some(Attachment {
+                    source: AttachmentSource::Path(PathBuf::from("/images/pikachu.png")),
+                    file_name: "SPOILER_pikachu.png".to_string(),
+                    oversized: Oversized::Fail,
+                })
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_links_videos() {
+        let messenger = RecordingMessenger::default();
+        let mut post = Post::example(5);
+        post.file_ext = "webm".to_string();
+        post.file_url = Some("https://static1.e621.net/data/5.webm".to_string());
+
+        publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &MessageStyle::default(),
+            || future::ready(Ok(post.clone())),
+        )
+        .await;
+
+        assert_eq!(
+            messenger.sent(),
+            vec![Sent::Post {
+                channel: CHANNEL,
+                content: Some("https://static1.e621.net/data/5.webm".to_string()),
+                attachment: None
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_returns_classified_search_errors() {
+        let messenger = RecordingMessenger::default();
+
+        let outcome = publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &MessageStyle::default(),
+            || future::ready(Err(Error::NoTagsSet)),
+        )
+        .await;
+
+        assert_eq!(
+            outcome,
+            PostOutcome::Failed(ErrorKind::BadQuery, "No tags have been set".to_string())
+        );
+        assert!(messenger.sent().is_empty());
+    }
+
+    #[tokio::test]
+    async fn publish_skips_posts_without_file() {
+        let messenger = RecordingMessenger::default();
+        let mut bad_post = Post::example(4);
+        bad_post.file_url = None;
+        let mut posts = vec![bad_post, Post::example(5)].into_iter();
+
+        let outcome = publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &MessageStyle::default(),
+            || future::ready(Ok(posts.next().unwrap())),
+        )
+        .await;
+
+        assert_eq!(
+            outcome,
+            PostOutcome::Posted(vec![Published { id: 5, md5: None }])
+        );
+        assert!(matches!(&messenger.sent()[..], [Sent::Post { .. }]));
+    }
+
+    #[tokio::test]
+    async fn publish_stops_after_repeated_bad_posts() {
+        let messenger = RecordingMessenger::default();
+        let mut post = Post::example(5);
+        post.file_url = None;
+
+        let mut attempts = 0;
+
+        let outcome = publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &MessageStyle::default(),
+            || {
+                attempts += 1;
+                future::ready(Ok(post.clone()))
+            },
+        )
+        .await;
+
+        assert_eq!(attempts, BAD_POST_ATTEMPTS);
+        assert!(matches!(outcome, PostOutcome::Stop(_)));
+        assert!(messenger.sent().is_empty());
+    }
+
+    fn gallery_style(format: PostFormat, gallery_size: u64) -> MessageStyle {
+        MessageStyle {
+            format,
+            gallery_size,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn publish_sends_galleries() {
+        let messenger = RecordingMessenger::default();
+        let mut posts = (1..).map(Post::example);
+
+        let outcome = publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &gallery_style(PostFormat::Gallery, 3),
+            || future::ready(Ok(posts.next().unwrap())),
+        )
+        .await;
+
+        let ids: Vec<u64> = match outcome {
+            PostOutcome::Posted(published) => published.iter().map(|post| post.id).collect(),
+            outcome => panic!("Nothing has been posted: {:?}", outcome),
+        };
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(
+            messenger.sent(),
+            vec![Sent::Gallery {
+                channel: CHANNEL,
+                contents: vec![None, None, None]
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_sends_smaller_galleries_when_the_search_runs_out() {
+        let messenger = RecordingMessenger::default();
+        let mut posts = vec![Post::example(1), Post::example(2), Post::example(1)].into_iter();
+
+        let outcome = publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &gallery_style(PostFormat::Gallery, 4),
+            || future::ready(Ok(posts.next().unwrap())),
+        )
+        .await;
+
+        assert!(matches!(outcome, PostOutcome::Posted(published) if published.len() == 2));
+        assert!(matches!(
+            &messenger.sent()[..],
+            [Sent::Gallery { contents, .. }] if contents.len() == 2
+        ));
+    }
+
+    #[tokio::test]
+    async fn galleries_show_every_post_which_is_reported() {
+        let messenger = RecordingMessenger::default();
+        let mut posts = (1..).map(|id| {
+            let mut post = Post::example(id);
+            if id == 2 {
+                post.tags.general = vec!["blood".to_string()];
             }
-            authors.remove(&interaction.message.id);
+            post
+        });
+        let style = MessageStyle {
+            spoiler_tags: vec!["blood".to_string()],
+            ..gallery_style(PostFormat::Gallery, 3)
+        };
+
+        let outcome = publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &style,
+            || future::ready(Ok(posts.next().unwrap())),
+        )
+        .await;
+
+        let published = match outcome {
+            PostOutcome::Posted(published) => published.len(),
+            outcome => panic!("Nothing has been posted: {:?}", outcome),
+        };
+        match &messenger.sent()[..] {
+            [Sent::Gallery { contents, .. }] => {
+                assert_eq!(contents.len(), published);
+                assert!(contents[1].as_ref().unwrap().contains("`blood`"));
+            }
+            sent => panic!("No gallery has been sent: {:?}", sent),
         }
     }
+
+    #[tokio::test]
+    async fn pages_are_flipped_through() {
+        let messenger = RecordingMessenger::default();
+        let galleries = Galleries::default();
+        let mut posts = (1..).map(Post::example);
+
+        publish(
+            &messenger,
+            &galleries,
+            GUILD,
+            CHANNEL,
+            &gallery_style(PostFormat::Pages, 3),
+            || future::ready(Ok(posts.next().unwrap())),
+        )
+        .await;
+        // the recording messenger numbers messages by the order they have been sent in
+        let message = MessageId(1);
+
+        handle_page_flip(&messenger, &galleries, &0, message, true).await;
+        handle_page_flip(&messenger, &galleries, &1, message, false).await;
+        handle_page_flip(&messenger, &galleries, &2, message, false).await;
+        handle_page_flip(&messenger, &galleries, &3, MessageId(10), true).await;
+
+        let shown = |interaction, content: &str| Sent::ShowPage {
+            interaction,
+            content: Some(content.to_string()),
+        };
+        assert_eq!(
+            messenger.sent(),
+            vec![
+                Sent::Pages {
+                    channel: CHANNEL,
+                    content: Some("Page 1/3".to_string())
+                },
+                shown(0, "Page 2/3"),
+                shown(1, "Page 1/3"),
+                shown(2, "Page 3/3"),
+                Sent::Acknowledge { interaction: 3 },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_with_files_are_sent_as_gallery() {
+        let messenger = RecordingMessenger::default();
+        let mut posts = (1..).map(|id| {
+            let mut post = Post::example(id);
+            post.file_path = Some(PathBuf::from(format!("/images/{}.png", id)));
+            post
+        });
+
+        publish(
+            &messenger,
+            &Galleries::default(),
+            GUILD,
+            CHANNEL,
+            &gallery_style(PostFormat::Pages, 2),
+            || future::ready(Ok(posts.next().unwrap())),
+        )
+        .await;
+
+        assert!(matches!(&messenger.sent()[..], [Sent::Gallery { .. }]));
+    }
+
+    #[test]
+    fn transient_errors_back_off_exponentially() {
+        assert_eq!(
+            recovery(ErrorKind::Transient, 1),
+            Recovery::Backoff(MINIMUM_TIMEOUT_MINUTES)
+        );
+        assert_eq!(
+            recovery(ErrorKind::Transient, 2),
+            Recovery::Backoff(MINIMUM_TIMEOUT_MINUTES * 2)
+        );
+        assert_eq!(
+            recovery(ErrorKind::Transient, 3),
+            Recovery::Backoff(MINIMUM_TIMEOUT_MINUTES * 4)
+        );
+        assert_eq!(
+            recovery(ErrorKind::Transient, 100),
+            Recovery::Backoff(MAXIMUM_TIMEOUT_MINUTES)
+        );
+    }
+
+    #[test]
+    fn persistent_errors_pause() {
+        assert_eq!(recovery(ErrorKind::Auth, 1), Recovery::Pause);
+        assert_eq!(recovery(ErrorKind::BadQuery, 1), Recovery::Pause);
+        assert_eq!(recovery(ErrorKind::Other, 1), Recovery::Retry);
+    }
+
+    #[test]
+    fn http_errors_are_classified() {
+        assert_eq!(ErrorKind::from_status(401), ErrorKind::Auth);
+        assert_eq!(ErrorKind::from_status(403), ErrorKind::Auth);
+        assert_eq!(ErrorKind::from_status(422), ErrorKind::BadQuery);
+        assert_eq!(ErrorKind::from_status(429), ErrorKind::Transient);
+        assert_eq!(ErrorKind::from_status(503), ErrorKind::Transient);
+    }
+
+    #[tokio::test]
+    async fn errors_are_reported_in_the_log_channel() {
+        let messenger = RecordingMessenger::default();
+        let log_channel = ChannelId(3);
+
+        report(&messenger, Some(log_channel), CHANNEL, "Oh no", true).await;
+
+        assert_eq!(
+            messenger.sent(),
+            vec![Sent::Say {
+                channel: log_channel,
+                content: format!("<#{}>: Oh no", CHANNEL)
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_without_log_channel() {
+        let messenger = RecordingMessenger::default();
+
+        report(&messenger, None, CHANNEL, "Trying again", false).await;
+        report(&messenger, None, CHANNEL, "Paused", true).await;
+
+        assert_eq!(
+            messenger.sent(),
+            vec![Sent::Say {
+                channel: CHANNEL,
+                content: "Paused".to_string()
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn enough_votes_delete_the_post() {
+        let messenger = RecordingMessenger::default();
+        let mut votes = DeleteVotes::default();
+        let message = MessageId(10);
+
+        for user in 0..DELETE_VOTES_NEEDED as u64 {
+            handle_delete_vote(
+                &messenger,
+                &mut votes,
+                &user,
+                CHANNEL,
+                message,
+                UserId(user),
+            )
+            .await;
+        }
+
+        let sent = messenger.sent();
+        for (current, sent) in sent.iter().take(DELETE_VOTES_NEEDED).enumerate() {
+            assert_eq!(
+                sent,
+                &Sent::UpdateDeleteButton {
+                    interaction: current as u64,
+                    current: current + 1,
+                    needed: DELETE_VOTES_NEEDED
+                }
+            );
+        }
+        assert_eq!(
+            sent.last(),
+            Some(&Sent::Delete {
+                channel: CHANNEL,
+                message
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn users_only_vote_once() {
+        let messenger = RecordingMessenger::default();
+        let mut votes = DeleteVotes::default();
+        let message = MessageId(10);
+
+        for interaction in 0..DELETE_VOTES_NEEDED as u64 {
+            handle_delete_vote(
+                &messenger,
+                &mut votes,
+                &interaction,
+                CHANNEL,
+                message,
+                UserId(1),
+            )
+            .await;
+        }
+
+        let sent = messenger.sent();
+        assert_eq!(sent.len(), DELETE_VOTES_NEEDED);
+        assert!(sent[1..]
+            .iter()
+            .all(|sent| matches!(sent, Sent::Acknowledge { .. })));
+        assert!(!sent.iter().any(|sent| matches!(sent, Sent::Delete { .. })));
+    }
+
+    #[tokio::test]
+    async fn votes_are_counted_per_message() {
+        let messenger = RecordingMessenger::default();
+        let mut votes = DeleteVotes::default();
+
+        handle_delete_vote(
+            &messenger,
+            &mut votes,
+            &0,
+            CHANNEL,
+            MessageId(10),
+            UserId(1),
+        )
+        .await;
+        handle_delete_vote(
+            &messenger,
+            &mut votes,
+            &1,
+            CHANNEL,
+            MessageId(11),
+            UserId(2),
+        )
+        .await;
+
+        assert_eq!(
+            messenger.sent()[1],
+            Sent::UpdateDeleteButton {
+                interaction: 1,
+                current: 1,
+                needed: DELETE_VOTES_NEEDED
+            }
+        );
+    }
 }
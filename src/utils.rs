@@ -1,25 +1,536 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
 use poise::serenity_prelude::{ButtonStyle, CreateActionRow, CreateEmbed, ReactionType};
-use rs621::post::Post;
-
-/// Create a discord embed from an e6/e9 post
-pub fn embed_from_post(post: &Post) -> Result<CreateEmbed, String> {
-    Ok(CreateEmbed::default()
-        .colour(0x203f6c_u32)
-        .title(format!("#{}", post.id))
-        .description(&post.description)
-        .url(&post.file.url.as_ref().ok_or("No url on post object")?)
-        .image(&post.file.url.as_ref().ok_or("No url on post object")?)
-        .field("Artist(s)", post.tags.artist.join(", "), false)
-        .footer(|footer| {
-            let score = format!(
-                "up: {}, down: {}, total: {}",
-                post.score.up,
-                post.score.down,
-                post.score.up + post.score.down,
+use serde_json::Value;
+
+use crate::{
+    configuration::{EmbedLayout, PostFormat},
+    constants::{
+        EMBEDS_TOTAL_LIMIT, EMBED_DESCRIPTION_LIMIT, EMBED_FIELD_LIMIT, MAXIMUM_GALLERY_SIZE,
+        SHORT_DESCRIPTION_LIMIT,
+    },
+    dtext,
+    filter::matching_tags,
+    sources::{MediaKind, Post, Rating},
+};
+
+/// How a channel wants its posts to be shown
+#[derive(Debug, Clone, Default)]
+pub struct MessageStyle {
+    pub layout: EmbedLayout,
+    /// posts with any of these tags are sent behind a spoiler. May contain `*` wildcards
+    pub spoiler_tags: Vec<String>,
+    pub format: PostFormat,
+    /// how many posts are sent at once by the gallery and pages formats
+    pub gallery_size: u64,
+    /// the pool the posts are pages of, which can be read ahead
+    pub pool: Option<u64>,
+}
+
+impl MessageStyle {
+    /// How many posts are sent together, at most as many as discord allows embeds in a message
+    pub fn posts_per_message(&self) -> usize {
+        match self.format {
+            PostFormat::Single => 1,
+            PostFormat::Gallery | PostFormat::Pages => {
+                self.gallery_size.clamp(1, MAXIMUM_GALLERY_SIZE) as usize
+            }
+        }
+    }
+}
+
+/// Everything needed to send a post to discord
+#[derive(Debug, Clone)]
+pub struct PostMessage {
+    /// text above the embed
+    pub content: Option<String>,
+    pub embed: CreateEmbed,
+    /// file which is uploaded together with the embed
+    pub attachment: Option<Attachment>,
+    /// the page of a pool the pages after it can be read from
+    pub read_ahead: Option<ReadAhead>,
+}
+
+/// A page of a pool, whose button shows the page after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadAhead {
+    pub pool_id: u64,
+    pub post_id: u64,
+}
+
+impl ReadAhead {
+    /// The custom id of the button, which carries the page so it works after restarts as well
+    pub fn custom_id(&self) -> String {
+        format!("read-ahead:{}:{}", self.pool_id, self.post_id)
+    }
+
+    pub fn from_custom_id(custom_id: &str) -> Option<Self> {
+        let (pool_id, post_id) = custom_id.strip_prefix("read-ahead:")?.split_once(':')?;
+        Some(Self {
+            pool_id: pool_id.parse().ok()?,
+            post_id: post_id.parse().ok()?,
+        })
+    }
+}
+
+/// A file uploaded together with a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub source: AttachmentSource,
+    /// name of the file on discord. Files starting with `SPOILER_` are hidden until clicked
+    pub file_name: String,
+    pub oversized: Oversized,
+}
+
+/// What is sent instead of a file which is too large to upload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Oversized {
+    /// nothing, the message can't be sent without the file
+    Fail,
+    /// nothing, the message shows the post without the file as well
+    Skip,
+    /// a link to the file behind a spoiler
+    Link(String),
+}
+
+/// Where the file of an attachment comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttachmentSource {
+    /// a file on disk
+    Path(PathBuf),
+    /// a file which has to be downloaded first
+    Url(String),
+    /// a file which has been downloaded already
+    Data(Arc<[u8]>),
+}
+
+/// Create the whole message for a post.
+///
+/// Embeds can't play videos, so linked videos are put into the content as well,
+/// where discord shows a player for them.
+pub fn message_from_post(post: &Post, style: &MessageStyle) -> Result<PostMessage, String> {
+    let read_ahead = style.pool.map(|pool_id| ReadAhead {
+        pool_id,
+        post_id: post.id,
+    });
+
+    let spoilered = matching_tags(post, &style.spoiler_tags);
+    if !spoilered.is_empty() {
+        let mut message = spoiler_message(post, style.layout, &spoilered)?;
+        message.read_ahead = read_ahead;
+        return Ok(message);
+    }
+
+    let embed = embed_from_post(post, style.layout)?;
+    let content = match (post.media_kind(), &post.file_path) {
+        (MediaKind::Video, None) => post.file_url.clone(),
+        _ => None,
+    };
+    let attachment = match &post.file_path {
+        Some(path) => Some(Attachment {
+            source: AttachmentSource::Path(path.clone()),
+            file_name: file_name(path)?.to_string(),
+            oversized: Oversized::Fail,
+        }),
+        None => None,
+    };
+
+    Ok(PostMessage {
+        content,
+        embed,
+        attachment,
+        read_ahead,
+    })
+}
+
+/// Create the message for a post with spoiler tags, with a warning naming the tags.
+///
+/// Images in embeds can't be hidden, so the file is uploaded as a spoilered attachment instead.
+/// Videos are too large for that, they are linked behind a spoiler.
+fn spoiler_message(
+    post: &Post,
+    layout: EmbedLayout,
+    spoilered: &[&str],
+) -> Result<PostMessage, String> {
+    let media_kind = post.media_kind();
+    if media_kind == MediaKind::Flash {
+        return Err("Flash posts can not be shown".to_string());
+    }
+
+    let mut content = format!(
+        "⚠️ Content warning: {}",
+        spoilered
+            .iter()
+            .map(|tag| format!("`{}`", tag))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let attachment = match (&post.file_path, &post.file_url) {
+        (Some(path), _) => Some(Attachment {
+            source: AttachmentSource::Path(path.clone()),
+            file_name: format!("SPOILER_{}", file_name(path)?),
+            oversized: Oversized::Fail,
+        }),
+        (None, Some(url)) if media_kind == MediaKind::Video => {
+            // the angle brackets keep discord from showing a player outside of the spoiler
+            content.push_str(&format!("\n||<{}>||", url));
+            video_thumbnail(post)
+                .map(|thumbnail| spoiler_download(post, thumbnail, Oversized::Skip))
+        }
+        (None, Some(url)) => {
+            // samples are smaller, but only the ones of still images look the same
+            let download = match media_kind {
+                MediaKind::Image => post.sample_url.as_ref().unwrap_or(url),
+                _ => url,
+            };
+            Some(spoiler_download(
+                post,
+                download,
+                Oversized::Link(url.to_string()),
+            ))
+        }
+        (None, None) => return Err("No url on post object".to_string()),
+    };
+
+    let mut embed = CreateEmbed::default();
+    if let Some(url) = post.url.as_ref().or(post.file_url.as_ref()) {
+        embed.url(url);
+    }
+    add_details(&mut embed, post, layout);
+
+    Ok(PostMessage {
+        content: Some(content),
+        embed,
+        attachment,
+        read_ahead: None,
+    })
+}
+
+/// A spoilered attachment downloaded from a url, named after the post
+fn spoiler_download(post: &Post, url: &str, oversized: Oversized) -> Attachment {
+    let path = url.split('?').next().unwrap_or(url);
+    let ext = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+        .filter(|ext| !ext.contains('/'))
+        .unwrap_or(&post.file_ext);
+    let source = match &post.file_data {
+        // the file has been downloaded to hash it already
+        Some(data) if post.file_url.as_deref() == Some(url) => AttachmentSource::Data(data.clone()),
+        _ => AttachmentSource::Url(url.to_string()),
+    };
+    Attachment {
+        source,
+        file_name: format!("SPOILER_{}.{}", post.id, ext),
+        oversized,
+    }
+}
+
+fn file_name(path: &Path) -> Result<&str, String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| "Invalid file name on post object".to_string())
+}
+
+/// Create a discord embed from a post of any of the sites
+pub fn embed_from_post(post: &Post, layout: EmbedLayout) -> Result<CreateEmbed, String> {
+    let media_kind = post.media_kind();
+    if media_kind == MediaKind::Flash {
+        return Err("Flash posts can not be shown".to_string());
+    }
+
+    let mut embed = CreateEmbed::default();
+
+    // files on disk are uploaded together with the embed
+    match (&post.file_path, &post.file_url) {
+        (Some(path), _) => {
+            // discord shows uploaded videos by itself, outside of the embed
+            if media_kind != MediaKind::Video {
+                embed.attachment(file_name(path)?);
+            }
+            if let Some(url) = &post.url {
+                embed.url(url);
+            }
+        }
+        (None, Some(url)) if media_kind == MediaKind::Video => {
+            embed.url(post.url.as_ref().unwrap_or(url)).field(
+                "Video",
+                format!("[▶ Play]({})", url),
+                false,
             );
-            footer.text(score)
+            if let Some(thumbnail) = video_thumbnail(post) {
+                embed.image(thumbnail);
+            }
+        }
+        (None, Some(url)) => {
+            // links back to the post page, the file itself is shown anyway
+            embed.url(post.url.as_ref().unwrap_or(url)).image(url);
+        }
+        (None, None) => return Err("No url on post object".to_string()),
+    }
+
+    add_details(&mut embed, post, layout);
+    Ok(embed)
+}
+
+/// Adds everything but the file to an embed, as much as the layout asks for
+fn add_details(embed: &mut CreateEmbed, post: &Post, layout: EmbedLayout) {
+    embed.colour(0x203f6c_u32).title(
+        post.title
+            .clone()
+            .unwrap_or_else(|| format!("#{}", post.id)),
+    );
+
+    // sites without tag categories don't know the artist
+    embed.field("Artist(s)", tag_list(&post.tags.artist, "unknown"), false);
+
+    if layout >= EmbedLayout::Standard {
+        embed.field("Rating", rating_badge(post.rating), true);
+        if !post.tags.character.is_empty() {
+            embed.field("Characters", tag_list(&post.tags.character, ""), true);
+        }
+        if !post.tags.species.is_empty() {
+            embed.field("Species", tag_list(&post.tags.species, ""), true);
+        }
+        if let Some(created_at) = post.created_at {
+            embed.timestamp(created_at);
+        }
+        embed.footer(|footer| {
+            footer.text(format!(
+                "score: {} (up: {}, down: {}), favorites: {}",
+                post.score.total, post.score.up, post.score.down, post.fav_count,
+            ))
+        });
+    }
+
+    if layout >= EmbedLayout::Detailed {
+        if !post.tags.copyright.is_empty() {
+            embed.field("Copyright", tag_list(&post.tags.copyright, ""), true);
+        }
+        if !post.sources.is_empty() {
+            embed.field("Sources", source_links(&post.sources), false);
+        }
+    }
+
+    // the description gets what the rest leaves of the length discord allows an embed
+    let description_limit = match layout {
+        EmbedLayout::Minimal => 0,
+        EmbedLayout::Standard => SHORT_DESCRIPTION_LIMIT,
+        EmbedLayout::Detailed => EMBED_DESCRIPTION_LIMIT,
+    }
+    .min(EMBEDS_TOTAL_LIMIT.saturating_sub(embed_length(embed)));
+    if description_limit > 0 && !post.description.trim().is_empty() {
+        embed.description(dtext::to_markdown(
+            post.description.trim(),
+            site_url(post),
+            description_limit,
+        ));
+    }
+}
+
+/// The site a post is from, like `https://e621.net`, to resolve links in its description
+fn site_url(post: &Post) -> Option<&str> {
+    let url = post.url.as_deref()?;
+    let host_start = url.find("://")? + 3;
+    let host_end = url[host_start..]
+        .find('/')
+        .map(|end| host_start + end)
+        .unwrap_or(url.len());
+    Some(&url[..host_end])
+}
+
+/// A short, colored name of a rating
+fn rating_badge(rating: Rating) -> &'static str {
+    match rating {
+        Rating::Safe => "🟢 Safe",
+        Rating::Questionable => "🟡 Questionable",
+        Rating::Explicit => "🔴 Explicit",
+    }
+}
+
+/// Tags as a field value, with underscores shown as spaces
+fn tag_list(tags: &[String], empty: &str) -> String {
+    if tags.is_empty() {
+        return empty.to_string();
+    }
+    let tags = tags
+        .iter()
+        .map(|tag| tag.replace('_', " "))
+        .collect::<Vec<_>>()
+        .join(", ");
+    escape_truncated(&tags, EMBED_FIELD_LIMIT)
+}
+
+/// Links to the sources of a post, named by their domain.
+///
+/// Sources which aren't links, like the name of a magazine, are left as they are.
+fn source_links(sources: &[String]) -> String {
+    let mut links = String::new();
+    for source in sources {
+        let link = match source
+            .split_once("://")
+            .and_then(|(_, rest)| rest.split('/').next())
+        {
+            Some(domain) if !domain.is_empty() => {
+                format!("[{}]({})", domain.trim_start_matches("www."), source)
+            }
+            _ => escape_markdown(source),
+        };
+        // whole links only, a cut off link would be broken
+        if links.chars().count() + link.chars().count() + 1 > EMBED_FIELD_LIMIT {
+            break;
+        }
+        if !links.is_empty() {
+            links.push('\n');
+        }
+        links.push_str(&link);
+    }
+    links
+}
+
+/// Check if discord uses a character for formatting
+fn needs_escape(c: char) -> bool {
+    matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '[' | ']')
+}
+
+/// Escapes the characters discord uses for formatting
+pub(crate) fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if needs_escape(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes a text and shortens it to at most `limit` characters, like [truncate].
+///
+/// The text is cut before it is escaped, so a `\` is never cut off from the character it escapes
+fn escape_truncated(text: &str, limit: usize) -> String {
+    let escaped = escape_markdown(text);
+    if escaped.chars().count() <= limit {
+        return escaped;
+    }
+
+    // room for the `…`
+    let mut length = 1;
+    let cut: String = text
+        .chars()
+        .take_while(|c| {
+            length += if needs_escape(*c) { 2 } else { 1 };
+            length <= limit
+        })
+        .collect();
+    let mut truncated = escape_markdown(&cut);
+    truncated.push('…');
+    truncated
+}
+
+/// Shortens a text to at most `limit` characters, ending it with `…` if it has been cut
+pub fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(limit.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Joins words with spaces into at most `limit` characters.
+///
+/// Words are only left out as a whole, after the last one which fits, marked with ` ...`
+pub fn join_truncated(words: &[String], limit: usize) -> String {
+    let joined = words.join(" ");
+    if joined.chars().count() <= limit {
+        return joined;
+    }
+
+    let mut truncated = String::new();
+    let mut length = 0;
+    for word in words {
+        let separator = usize::from(!truncated.is_empty());
+        let word_length = word.chars().count();
+        if length + separator + word_length + " ...".len() > limit {
+            break;
+        }
+        if separator == 1 {
+            truncated.push(' ');
+        }
+        truncated.push_str(word);
+        length += separator + word_length;
+    }
+    if truncated.is_empty() {
+        "...".to_string()
+    } else {
+        truncated.push_str(" ...");
+        truncated
+    }
+}
+
+/// Counts the characters of an embed the way discord does for its limits
+pub fn embed_length(embed: &CreateEmbed) -> usize {
+    let text = |value: Option<&Value>| {
+        value
+            .and_then(Value::as_str)
+            .map_or(0, |text| text.chars().count())
+    };
+    let fields = embed
+        .0
+        .get("fields")
+        .and_then(Value::as_array)
+        .map_or(0, |fields| {
+            fields
+                .iter()
+                .map(|field| text(field.get("name")) + text(field.get("value")))
+                .sum()
+        });
+
+    text(embed.0.get("title"))
+        + text(embed.0.get("description"))
+        + text(embed.0.get("footer").and_then(|footer| footer.get("text")))
+        + text(embed.0.get("author").and_then(|author| author.get("name")))
+        + fields
+}
+
+/// Groups embeds into as few messages as discord allows, keeping their order
+pub fn split_embeds(embeds: Vec<CreateEmbed>) -> Vec<Vec<CreateEmbed>> {
+    let mut messages: Vec<Vec<CreateEmbed>> = Vec::new();
+    // characters of the embeds of the last message
+    let mut length = 0;
+    for embed in embeds {
+        let embed_length = embed_length(&embed);
+        match messages.last_mut() {
+            Some(message)
+                if message.len() < MAXIMUM_GALLERY_SIZE as usize
+                    && length + embed_length <= EMBEDS_TOTAL_LIMIT =>
+            {
+                length += embed_length;
+                message.push(embed);
+            }
+            _ => {
+                length = embed_length;
+                messages.push(vec![embed]);
+            }
+        }
+    }
+    messages
+}
+
+/// A still image of a video. Some sites use the video itself as the sample
+fn video_thumbnail(post: &Post) -> Option<&String> {
+    [&post.sample_url, &post.preview_url]
+        .into_iter()
+        .flatten()
+        .find(|url| {
+            let ext = url.rsplit_once('.').map(|(_, ext)| ext).unwrap_or_default();
+            MediaKind::from_ext(ext) == MediaKind::Image
         })
-        .to_owned())
 }
 
 pub fn post_buttons(current: usize, needed: usize) -> CreateActionRow {
@@ -34,3 +545,282 @@ pub fn post_buttons(current: usize, needed: usize) -> CreateActionRow {
 
     action_row
 }
+
+/// Buttons to flip through the pages of a message, below its delete button
+pub fn page_buttons() -> CreateActionRow {
+    let mut action_row = CreateActionRow::default();
+    action_row.create_button(|previous_button| {
+        previous_button
+            .custom_id("previous-page")
+            .emoji(ReactionType::Unicode("◀️".to_string()))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+    });
+    action_row.create_button(|next_button| {
+        next_button
+            .custom_id("next-page")
+            .emoji(ReactionType::Unicode("▶️".to_string()))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+    });
+
+    action_row
+}
+
+/// Button to read the pages of a pool after the one which has been posted
+pub fn read_ahead_button(read_ahead: ReadAhead) -> CreateActionRow {
+    let mut action_row = CreateActionRow::default();
+    action_row.create_button(|read_ahead_button| {
+        read_ahead_button
+            .custom_id(read_ahead.custom_id())
+            .emoji(ReactionType::Unicode("📖".to_string()))
+            .label("Read ahead")
+            .style(ButtonStyle::Secondary)
+    });
+
+    action_row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::{PostScore, PostTags};
+
+    fn post() -> Post {
+        Post {
+            id: 1,
+            url: Some("https://e621.net/posts/1".to_string()),
+            file_url: Some("https://static1.e621.net/data/1.png".to_string()),
+            file_ext: "png".to_string(),
+            rating: Rating::Safe,
+            tags: PostTags {
+                artist: vec!["some_artist".to_string()],
+                character: vec!["pikachu".to_string()],
+                species: vec!["pokémon_(species)".to_string()],
+                copyright: vec!["pokémon".to_string()],
+                ..Default::default()
+            },
+            score: PostScore {
+                up: 10,
+                down: -2,
+                total: 8,
+            },
+            fav_count: 5,
+            sources: vec!["https://www.example.com/art/1".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn field_names(embed: &CreateEmbed) -> Vec<&str> {
+        embed.0["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|field| field["name"].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn minimal_layout_only_names_the_artists() {
+        let embed = embed_from_post(&post(), EmbedLayout::Minimal).unwrap();
+
+        assert_eq!(field_names(&embed), vec!["Artist(s)"]);
+        assert!(!embed.0.contains_key("footer"));
+    }
+
+    #[test]
+    fn standard_layout_shows_rating_characters_species_and_score() {
+        let embed = embed_from_post(&post(), EmbedLayout::Standard).unwrap();
+
+        assert_eq!(
+            field_names(&embed),
+            vec!["Artist(s)", "Rating", "Characters", "Species"]
+        );
+        assert_eq!(
+            embed.0["footer"]["text"],
+            Value::from("score: 8 (up: 10, down: -2), favorites: 5")
+        );
+    }
+
+    #[test]
+    fn detailed_layout_adds_copyright_and_sources() {
+        let embed = embed_from_post(&post(), EmbedLayout::Detailed).unwrap();
+
+        assert_eq!(
+            field_names(&embed),
+            vec![
+                "Artist(s)",
+                "Rating",
+                "Characters",
+                "Species",
+                "Copyright",
+                "Sources"
+            ]
+        );
+    }
+
+    #[test]
+    fn flash_posts_can_not_be_shown() {
+        let mut post = post();
+        post.file_ext = "swf".to_string();
+
+        assert!(embed_from_post(&post, EmbedLayout::Standard).is_err());
+    }
+
+    #[test]
+    fn truncates_long_texts() {
+        assert_eq!(truncate("pikachu", 7), "pikachu");
+        assert_eq!(truncate("pikachu", 5), "pika…");
+        assert_eq!(truncate("pokémon", 4), "pok…");
+    }
+
+    #[test]
+    fn tag_lists_show_underscores_as_spaces() {
+        let tags = vec!["pokémon_(species)".to_string(), "*star*".to_string()];
+
+        assert_eq!(tag_list(&tags, ""), "pokémon (species), \\*star\\*");
+        assert_eq!(tag_list(&[], "unknown"), "unknown");
+    }
+
+    #[test]
+    fn tag_lists_are_not_cut_between_escapes() {
+        let tags = vec!["*".repeat(EMBED_FIELD_LIMIT)];
+        let list = tag_list(&tags, "");
+
+        assert!(list.chars().count() <= EMBED_FIELD_LIMIT);
+        assert!(list.ends_with("\\*…"));
+        assert!(!list.ends_with("\\…"));
+    }
+
+    #[test]
+    fn source_links_are_named_by_their_domain() {
+        let sources = vec![
+            "https://www.example.com/art/1".to_string(),
+            "Some *magazine*".to_string(),
+        ];
+
+        assert_eq!(
+            source_links(&sources),
+            "[example.com](https://www.example.com/art/1)\nSome \\*magazine\\*"
+        );
+    }
+
+    #[test]
+    fn source_links_only_keep_whole_links() {
+        let sources: Vec<String> = (0..100)
+            .map(|i| format!("https://example.com/{}", "a".repeat(i)))
+            .collect();
+        let links = source_links(&sources);
+
+        assert!(links.chars().count() <= EMBED_FIELD_LIMIT);
+        assert!(links.lines().all(|link| link.ends_with(')')));
+    }
+
+    #[test]
+    fn detailed_embeds_stay_within_the_length_discord_allows() {
+        let tags =
+            |name: &str| -> Vec<String> { (0..200).map(|i| format!("{}_{}", name, i)).collect() };
+        let mut post = post();
+        post.title = Some("a".repeat(256));
+        post.description = "bb ".repeat(EMBED_DESCRIPTION_LIMIT);
+        post.tags.artist = tags("artist");
+        post.tags.character = tags("character");
+        post.tags.species = tags("species");
+        post.tags.copyright = tags("copyright");
+        post.sources = (0..100)
+            .map(|i| format!("https://example.com/{}", i))
+            .collect();
+
+        let embed = embed_from_post(&post, EmbedLayout::Detailed).unwrap();
+
+        assert!(embed_length(&embed) <= EMBEDS_TOTAL_LIMIT);
+        // the description is only cut as far as needed
+        assert!(embed_length(&embed) > EMBEDS_TOTAL_LIMIT - 10);
+    }
+
+    fn words(count: usize, word: &str) -> Vec<String> {
+        (0..count).map(|_| word.to_string()).collect()
+    }
+
+    #[test]
+    fn joins_words_which_fit() {
+        assert_eq!(join_truncated(&words(3, "ab"), 8), "ab ab ab");
+    }
+
+    #[test]
+    fn leaves_out_whole_words_which_do_not_fit() {
+        assert_eq!(join_truncated(&words(3, "ab"), 7), "ab ...");
+        assert_eq!(join_truncated(&words(2, "abcdef"), 7), "...");
+    }
+
+    #[test]
+    fn counts_characters_of_joined_words() {
+        let joined = join_truncated(&words(300, "pokémon"), EMBED_FIELD_LIMIT);
+
+        assert!(joined.chars().count() <= EMBED_FIELD_LIMIT);
+        // bytes would have fit fewer words
+        assert!(joined.chars().count() > EMBED_FIELD_LIMIT - "pokémon ...".len());
+    }
+
+    #[test]
+    fn counts_the_text_of_embeds() {
+        let mut embed = CreateEmbed::default();
+        embed
+            .title("ab")
+            .description("cdé")
+            .field("fg", "hi", false)
+            .footer(|f| f.text("jk"));
+
+        assert_eq!(embed_length(&embed), 11);
+    }
+
+    #[test]
+    fn splits_embeds_by_count_and_length() {
+        let embed = |length: usize| {
+            let mut embed = CreateEmbed::default();
+            embed.description("a".repeat(length));
+            embed
+        };
+
+        let by_count: Vec<usize> = split_embeds((0..12).map(|_| embed(1)).collect())
+            .iter()
+            .map(Vec::len)
+            .collect();
+        assert_eq!(by_count, vec![10, 2]);
+
+        let by_length: Vec<usize> = split_embeds((0..3).map(|_| embed(2500)).collect())
+            .iter()
+            .map(Vec::len)
+            .collect();
+        assert_eq!(by_length, vec![2, 1]);
+    }
+
+    #[test]
+    fn spoilered_files_are_not_downloaded_twice() {
+        let mut post = post();
+        post.file_data = Some(Arc::from(&b"image"[..]));
+
+        let message = spoiler_message(&post, EmbedLayout::Minimal, &["gore"]).unwrap();
+        let attachment = message.attachment.unwrap();
+
+        assert_eq!(
+            attachment.source,
+            AttachmentSource::Data(Arc::from(&b"image"[..]))
+        );
+        assert_eq!(attachment.file_name, "SPOILER_1.png");
+    }
+
+    #[test]
+    fn spoilered_samples_are_downloaded() {
+        let mut post = post();
+        post.file_data = Some(Arc::from(&b"image"[..]));
+        post.sample_url = Some("https://static1.e621.net/data/sample/1.jpg".to_string());
+
+        let message = spoiler_message(&post, EmbedLayout::Minimal, &["gore"]).unwrap();
+
+        assert_eq!(
+            message.attachment.unwrap().source,
+            AttachmentSource::Url("https://static1.e621.net/data/sample/1.jpg".to_string())
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! A local mock of the e621 api, serving canned responses
+#![allow(dead_code)]
+
+use cutepokebot::{http::HttpSettings, sources::e621::E621Source};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+pub struct MockE621 {
+    pub server: MockServer,
+}
+
+impl MockE621 {
+    pub async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+        }
+    }
+
+    /// An e621 source with both the e621 and the e926 client pointed at the mock
+    pub fn source(&self) -> E621Source {
+        E621Source::new(
+            &self.server.uri(),
+            &self.server.uri(),
+            &http_settings(),
+            None,
+        )
+        .unwrap()
+    }
+
+    /// Serves a single page of posts. Every page after it is empty
+    pub async fn serve_posts(&self, posts: Vec<Value>) {
+        Mock::given(method("GET"))
+            .and(path("/posts.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "posts": posts })))
+            .up_to_n_times(1)
+            .mount(&self.server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/posts.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "posts": [] })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers every search with an error, like e621 does while it is down
+    pub async fn serve_error(&self, status: u16) {
+        Mock::given(method("GET"))
+            .and(path("/posts.json"))
+            .respond_with(ResponseTemplate::new(status).set_body_json(json!({
+                "success": false,
+                "reason": "Internal error",
+            })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Answers every search the way e621 does when too many requests have been made
+    pub async fn serve_rate_limit(&self) {
+        Mock::given(method("GET"))
+            .and(path("/posts.json"))
+            .respond_with(ResponseTemplate::new(503).set_body_json(json!({
+                "success": false,
+                "reason": "Rate limit exceeded",
+            })))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// The tags of every search the mock has received, in order
+    pub async fn received_searches(&self) -> Vec<Vec<String>> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|request| {
+                request
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == "tags")
+                    .map(|(_, tags)| tags.split_whitespace().map(|s| s.to_string()).collect())
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+/// Settings for clients talking to a mock, without a proxy
+pub fn http_settings() -> HttpSettings {
+    HttpSettings::new("tests", None)
+}
+
+/// A post in the format of `/posts.json`. `rating` is `s`, `q` or `e`
+pub fn e621_post(id: u64, rating: &str, general: &[&str]) -> Value {
+    json!({
+        "id": id,
+        "created_at": "2022-06-01T12:00:00.000-04:00",
+        "updated_at": "2022-06-02T12:00:00.000-04:00",
+        "file": {
+            "width": 1000,
+            "height": 800,
+            "ext": "png",
+            "size": 123456,
+            "md5": format!("{:032x}", id),
+            "url": format!("https://static1.e621.net/data/{:032x}.png", id),
+        },
+        "preview": {
+            "width": 150,
+            "height": 120,
+            "url": format!("https://static1.e621.net/data/preview/{:032x}.jpg", id),
+        },
+        "sample": {
+            "has": false,
+            "height": 800,
+            "width": 1000,
+            "url": format!("https://static1.e621.net/data/{:032x}.png", id),
+            "alternates": {},
+        },
+        "score": { "up": 10, "down": -2, "total": 8 },
+        "tags": {
+            "general": general,
+            "species": ["pokémon_(species)"],
+            "character": [],
+            "copyright": ["pokémon"],
+            "artist": ["some_artist"],
+            "invalid": [],
+            "lore": [],
+            "meta": [],
+        },
+        "locked_tags": [],
+        "change_seq": id,
+        "flags": {
+            "pending": false,
+            "flagged": false,
+            "note_locked": false,
+            "status_locked": false,
+            "rating_locked": false,
+            "deleted": false,
+        },
+        "rating": rating,
+        "fav_count": 5,
+        "sources": ["https://example.com/art"],
+        "pools": [],
+        "relationships": {
+            "parent_id": null,
+            "has_children": false,
+            "has_active_children": false,
+            "children": [],
+        },
+        "approver_id": null,
+        "uploader_id": 1,
+        "description": "",
+        "comment_count": 0,
+        "is_favorited": false,
+        "has_notes": false,
+        "duration": null,
+    })
+}
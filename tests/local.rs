@@ -0,0 +1,129 @@
+//! Searching curated images in a directory on disk
+
+use std::path::PathBuf;
+
+use cutepokebot::{
+    configuration::NsfwMode,
+    sources::{local::LocalSource, Post, PostSource, Rating},
+};
+use futures::TryStreamExt;
+
+/// A fresh directory for one test, removed again by [remove_directory]
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("cutepokebot-{}", name));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn remove_directory(directory: &PathBuf) {
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+async fn search(source: &LocalSource, tags: &[&str]) -> Vec<Post> {
+    let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+    let mut posts: Vec<Post> = source
+        .search(NsfwMode::NSFW, &tags)
+        .try_collect()
+        .await
+        .unwrap();
+    posts.sort_by_key(|post| post.file_path.clone());
+    posts
+}
+
+fn file_names(posts: &[Post]) -> Vec<String> {
+    posts
+        .iter()
+        .filter_map(|post| post.file_path.as_ref()?.file_name()?.to_str())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn reads_images_with_their_json_files() {
+    let directory = directory("reads-images-with-their-json-files");
+    std::fs::write(directory.join("pikachu.png"), "image").unwrap();
+    std::fs::write(
+        directory.join("pikachu.json"),
+        r#"{"artist": "some_artist", "title": "Pikachu", "tags": ["Pikachu", "solo"], "rating": "questionable"}"#,
+    )
+    .unwrap();
+    std::fs::write(directory.join("eevee.JPG"), "image").unwrap();
+    std::fs::write(directory.join("notes.txt"), "not an image").unwrap();
+
+    let source = LocalSource::new(Some(directory.clone()));
+    let posts = search(&source, &[]).await;
+    remove_directory(&directory);
+
+    assert_eq!(file_names(&posts), vec!["eevee.JPG", "pikachu.png"]);
+
+    let eevee = &posts[0];
+    assert_eq!(eevee.file_ext, "jpg");
+    assert_eq!(eevee.rating, Rating::Safe);
+    assert!(eevee.tags.general.is_empty());
+
+    let pikachu = &posts[1];
+    assert_eq!(pikachu.title.as_deref(), Some("Pikachu"));
+    assert_eq!(pikachu.rating, Rating::Questionable);
+    assert_eq!(pikachu.tags.general, vec!["pikachu", "solo"]);
+    assert_eq!(pikachu.tags.artist, vec!["some_artist"]);
+}
+
+#[tokio::test]
+async fn searches_by_tags_and_artist() {
+    let directory = directory("searches-by-tags-and-artist");
+    std::fs::write(directory.join("pikachu.png"), "image").unwrap();
+    std::fs::write(
+        directory.join("pikachu.json"),
+        r#"{"artist": "some_artist", "tags": ["pikachu"]}"#,
+    )
+    .unwrap();
+    std::fs::write(directory.join("eevee.png"), "image").unwrap();
+    std::fs::write(directory.join("eevee.json"), r#"{"tags": ["eevee"]}"#).unwrap();
+
+    let source = LocalSource::new(Some(directory.clone()));
+    let by_tag = search(&source, &["pikachu"]).await;
+    let by_artist = search(&source, &["some_artist"]).await;
+    let excluded = search(&source, &["-pikachu"]).await;
+    remove_directory(&directory);
+
+    assert_eq!(file_names(&by_tag), vec!["pikachu.png"]);
+    assert_eq!(file_names(&by_artist), vec!["pikachu.png"]);
+    assert_eq!(file_names(&excluded), vec!["eevee.png"]);
+}
+
+#[tokio::test]
+async fn broken_json_files_are_ignored() {
+    let directory = directory("broken-json-files-are-ignored");
+    std::fs::write(directory.join("pikachu.png"), "image").unwrap();
+    std::fs::write(directory.join("pikachu.json"), "{ not json").unwrap();
+    std::fs::write(directory.join("eevee.png"), "image").unwrap();
+
+    let source = LocalSource::new(Some(directory.clone()));
+    let posts = search(&source, &[]).await;
+    remove_directory(&directory);
+
+    assert_eq!(file_names(&posts), vec!["eevee.png", "pikachu.png"]);
+    assert!(posts[1].tags.general.is_empty());
+}
+
+#[tokio::test]
+async fn images_with_spaces_in_their_name_are_skipped() {
+    let directory = directory("images-with-spaces-are-skipped");
+    std::fs::write(directory.join("pikachu and eevee.png"), "image").unwrap();
+    std::fs::write(directory.join("eevee.png"), "image").unwrap();
+
+    let source = LocalSource::new(Some(directory.clone()));
+    let posts = search(&source, &[]).await;
+    remove_directory(&directory);
+
+    assert_eq!(file_names(&posts), vec!["eevee.png"]);
+}
+
+#[tokio::test]
+async fn searching_without_a_directory_fails() {
+    let source = LocalSource::new(None);
+    let result: Result<Vec<Post>, _> = source.search(NsfwMode::NSFW, &[]).try_collect().await;
+
+    assert!(result.is_err());
+}
@@ -0,0 +1,101 @@
+//! Looking up pools against a local mock of the e621 api
+
+mod common;
+
+use common::{e621_post, http_settings, MockE621};
+use cutepokebot::{configuration::NsfwMode, error::Error, pool_api::PoolApi};
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+fn pool_api(mock: &MockE621) -> PoolApi {
+    PoolApi::new(&mock.server.uri(), &mock.server.uri(), &http_settings()).unwrap()
+}
+
+#[tokio::test]
+async fn looks_up_pools() {
+    let mock = MockE621::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pools/42.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 42,
+            "name": "Pikachu's_big_day",
+            "post_ids": [3, 1, 2],
+            "category": "series",
+            "is_active": true,
+        })))
+        .mount(&mock.server)
+        .await;
+
+    let pool = pool_api(&mock).pool(NsfwMode::NSFW, 42).await.unwrap();
+
+    assert_eq!(pool.id, 42);
+    assert_eq!(pool.title(), "Pikachu's big day");
+    assert_eq!(pool.post_ids, vec![3, 1, 2]);
+}
+
+#[tokio::test]
+async fn random_pools_are_found_through_their_posts() {
+    let mock = MockE621::start().await;
+    let mut post = e621_post(1, "s", &["pikachu", "comic"]);
+    post["pools"] = json!([42]);
+    mock.serve_posts(vec![post]).await;
+
+    let pool_id = pool_api(&mock)
+        .random_pool(NsfwMode::NSFW, &["pikachu".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(pool_id, 42);
+    let searches = mock.received_searches().await;
+    assert!(searches[0].contains(&"pikachu".to_string()));
+    assert!(searches[0].contains(&"inpool:true".to_string()));
+}
+
+#[tokio::test]
+async fn no_pool_is_found_without_posts() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(Vec::new()).await;
+
+    let result = pool_api(&mock)
+        .random_pool(NsfwMode::NSFW, &["pikachu".to_string()])
+        .await;
+
+    assert!(matches!(result, Err(Error::NoPoolFound)));
+}
+
+#[tokio::test]
+async fn random_pools_leave_out_excluded_tags_which_do_not_fit() {
+    let mock = MockE621::start().await;
+    let mut post = e621_post(1, "s", &["pikachu"]);
+    post["pools"] = json!([42]);
+    mock.serve_posts(vec![post]).await;
+    let mut tags = vec!["pikachu".to_string()];
+    tags.extend((0..50).map(|i| format!("-tag_{}", i)));
+
+    let pool_id = pool_api(&mock)
+        .random_pool(NsfwMode::NSFW, &tags)
+        .await
+        .unwrap();
+
+    assert_eq!(pool_id, 42);
+    let searches = mock.received_searches().await;
+    assert_eq!(searches[0].len(), 40);
+    assert!(searches[0].contains(&"pikachu".to_string()));
+    assert!(searches[0].contains(&"inpool:true".to_string()));
+    assert!(searches[0].contains(&"order:random".to_string()));
+}
+
+#[tokio::test]
+async fn random_pools_reject_too_many_tags() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(Vec::new()).await;
+    let tags: Vec<String> = (0..39).map(|i| format!("tag_{}", i)).collect();
+
+    let result = pool_api(&mock).random_pool(NsfwMode::NSFW, &tags).await;
+
+    assert!(matches!(result, Err(Error::TooManyTags(_))));
+    assert!(mock.received_searches().await.is_empty());
+}
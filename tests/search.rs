@@ -0,0 +1,416 @@
+//! Searches against a local mock of the e621 api
+
+mod common;
+
+use std::collections::HashSet;
+
+use common::{e621_post, http_settings, MockE621};
+use cutepokebot::{
+    configuration::{MediaMode, NsfwMode},
+    constants::MAXIMUM_SEARCH_TAGS,
+    error::Error,
+    sources::{
+        danbooru::DanbooruSource, find_new_post, gelbooru::GelbooruSource, MediaKind, Rating,
+    },
+};
+use serde_json::{json, Value};
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn tags(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|tag| tag.to_string()).collect()
+}
+
+#[tokio::test]
+async fn finds_post_from_canned_page() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(vec![e621_post(1, "s", &["pikachu", "solo"])])
+        .await;
+
+    let post = find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(post.id, 1);
+    assert_eq!(post.rating, Rating::Safe);
+    assert_eq!(post.url, Some(format!("{}/posts/1", mock.server.uri())));
+    assert!(post.tags.general.contains(&"pikachu".to_string()));
+    assert_eq!(post.score.total, 8);
+
+    let searches = mock.received_searches().await;
+    assert!(searches[0].contains(&"pikachu".to_string()));
+    assert!(searches[0].contains(&"order:random".to_string()));
+}
+
+#[tokio::test]
+async fn skips_blacklisted_posts() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(vec![
+        e621_post(1, "s", &["pikachu", "gore"]),
+        e621_post(2, "s", &["pikachu"]),
+    ])
+    .await;
+
+    let post = find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &tags(&["gore"]),
+        &HashSet::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(post.id, 2);
+}
+
+#[tokio::test]
+async fn skips_posts_which_have_been_posted_before() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(vec![
+        e621_post(1, "s", &["pikachu"]),
+        e621_post(2, "s", &["pikachu"]),
+    ])
+    .await;
+
+    let known_hashes = HashSet::from([format!("{:032x}", 1)]);
+    let post = find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &known_hashes,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(post.id, 2);
+    assert_eq!(post.md5, Some(format!("{:032x}", 2)));
+}
+
+/// A pikachu post whose file has the extension `ext`
+fn post_with_ext(id: u64, ext: &str) -> Value {
+    let mut post = e621_post(id, "s", &["pikachu"]);
+    post["file"]["ext"] = json!(ext);
+    post
+}
+
+/// Searches a fresh mock serving a webm, a gif, a flash and a png post, in this order
+async fn find_with_media_mode(media_mode: MediaMode) -> Option<u64> {
+    let mock = MockE621::start().await;
+    mock.serve_posts(vec![
+        post_with_ext(1, "swf"),
+        post_with_ext(2, "webm"),
+        post_with_ext(3, "gif"),
+        post_with_ext(4, "png"),
+    ])
+    .await;
+
+    find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        media_mode,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await
+    .ok()
+    .map(|post| post.id)
+}
+
+#[tokio::test]
+async fn skips_flash_posts() {
+    assert_eq!(find_with_media_mode(MediaMode::All).await, Some(2));
+}
+
+#[tokio::test]
+async fn skips_posts_the_media_mode_does_not_allow() {
+    assert_eq!(find_with_media_mode(MediaMode::NoVideos).await, Some(3));
+    assert_eq!(find_with_media_mode(MediaMode::StillImages).await, Some(4));
+    assert_eq!(find_with_media_mode(MediaMode::AnimatedOnly).await, Some(2));
+}
+
+#[test]
+fn media_modes_allow_kinds_of_files() {
+    let allowed = |media_mode: MediaMode| -> Vec<MediaKind> {
+        [
+            MediaKind::Image,
+            MediaKind::Animated,
+            MediaKind::Video,
+            MediaKind::Flash,
+        ]
+        .into_iter()
+        .filter(|kind| media_mode.allows(*kind))
+        .collect()
+    };
+
+    assert_eq!(
+        allowed(MediaMode::All),
+        vec![MediaKind::Image, MediaKind::Animated, MediaKind::Video]
+    );
+    assert_eq!(
+        allowed(MediaMode::NoVideos),
+        vec![MediaKind::Image, MediaKind::Animated]
+    );
+    assert_eq!(allowed(MediaMode::StillImages), vec![MediaKind::Image]);
+    assert_eq!(
+        allowed(MediaMode::AnimatedOnly),
+        vec![MediaKind::Animated, MediaKind::Video]
+    );
+}
+
+#[tokio::test]
+async fn filters_excluded_tags_over_the_search_limit() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(vec![
+        e621_post(1, "s", &["pikachu", "excluded_last"]),
+        e621_post(2, "s", &["pikachu"]),
+    ])
+    .await;
+
+    let mut query = vec!["pikachu".to_string()];
+    query.extend((0..MAXIMUM_SEARCH_TAGS).map(|i| format!("-excluded_{}", i)));
+    query.push("-excluded_last".to_string());
+
+    let post = find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        MediaMode::All,
+        query,
+        &[],
+        &HashSet::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(post.id, 2);
+    for search in mock.received_searches().await {
+        assert!(search.len() <= MAXIMUM_SEARCH_TAGS);
+    }
+}
+
+#[tokio::test]
+async fn sfw_mode_only_allows_safe_posts() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(vec![
+        e621_post(1, "q", &["pikachu"]),
+        e621_post(2, "e", &["pikachu"]),
+        e621_post(3, "s", &["pikachu"]),
+    ])
+    .await;
+
+    let post = find_new_post(
+        &mock.source(),
+        NsfwMode::SFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(post.id, 3);
+}
+
+#[tokio::test]
+async fn no_results() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(Vec::new()).await;
+
+    let result = find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await;
+
+    assert!(matches!(result, Err(Error::Uhhh(_))));
+}
+
+#[tokio::test]
+async fn only_filtered_results() {
+    let mock = MockE621::start().await;
+    mock.serve_posts(vec![e621_post(1, "s", &["pikachu", "gore"])])
+        .await;
+
+    let result = find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &tags(&["gore"]),
+        &HashSet::new(),
+    )
+    .await;
+
+    assert!(matches!(result, Err(Error::Uhhh(_))));
+}
+
+#[tokio::test]
+async fn server_error() {
+    let mock = MockE621::start().await;
+    mock.serve_error(500).await;
+
+    let result = find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::Rs621(rs621::error::Error::Http { code: 500, .. }))
+    ));
+}
+
+#[tokio::test]
+async fn rate_limited() {
+    let mock = MockE621::start().await;
+    mock.serve_rate_limit().await;
+
+    let result = find_new_post(
+        &mock.source(),
+        NsfwMode::NSFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(Error::Rs621(rs621::error::Error::Http { code: 503, .. }))
+    ));
+}
+
+#[tokio::test]
+async fn danbooru_search() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/posts.json"))
+        .and(query_param("random", "true"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {
+                "id": 1,
+                "rating": "e",
+                "file_ext": "png",
+                "file_url": "https://cdn.donmai.us/original/1.png",
+                "tag_string_general": "pikachu solo",
+            },
+            {
+                "id": 2,
+                "rating": "g",
+                "file_ext": "png",
+                "file_url": "https://cdn.donmai.us/original/2.png",
+                "tag_string_general": "pikachu",
+                "tag_string_artist": "some_artist",
+            },
+        ])))
+        .mount(&server)
+        .await;
+    let source = DanbooruSource::new(&server.uri(), &server.uri(), &http_settings(), None).unwrap();
+
+    let post = find_new_post(
+        &source,
+        NsfwMode::SFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(post.id, 2);
+    assert_eq!(post.tags.artist, tags(&["some_artist"]));
+}
+
+#[tokio::test]
+async fn gelbooru_search() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/index.php"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "@attributes": { "limit": 100, "offset": 0, "count": 1 },
+            "post": [
+                {
+                    "id": 7,
+                    "md5": "0123456789abcdef0123456789abcdef",
+                    "image": "0123456789abcdef0123456789abcdef.jpg",
+                    "file_url": "https://img3.gelbooru.com/images/01/23/0123456789abcdef0123456789abcdef.jpg",
+                    "rating": "general",
+                    "score": 3,
+                    "tags": "pikachu solo",
+                    "source": "",
+                },
+            ],
+        })))
+        .mount(&server)
+        .await;
+    let source = GelbooruSource::new(
+        &server.uri(),
+        Some("rating:general"),
+        &http_settings(),
+        None,
+    )
+    .unwrap();
+
+    let post = find_new_post(
+        &source,
+        NsfwMode::SFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(post.id, 7);
+    assert_eq!(post.file_ext, "jpg");
+    assert_eq!(post.rating, Rating::Safe);
+}
+
+#[tokio::test]
+async fn gelbooru_empty_response() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/index.php"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    let source = GelbooruSource::new(&server.uri(), None, &http_settings(), None).unwrap();
+
+    let result = find_new_post(
+        &source,
+        NsfwMode::NSFW,
+        MediaMode::All,
+        tags(&["pikachu"]),
+        &[],
+        &HashSet::new(),
+    )
+    .await;
+
+    assert!(matches!(result, Err(Error::Uhhh(_))));
+}
@@ -0,0 +1,55 @@
+//! Looking up tags against a local mock of the e621 api
+
+mod common;
+
+use common::{http_settings, MockE621};
+use cutepokebot::tag_api::TagApi;
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path, query_param},
+    Mock, ResponseTemplate,
+};
+
+fn tag_api(mock: &MockE621) -> TagApi {
+    TagApi::new(&mock.server.uri(), &http_settings()).unwrap()
+}
+
+#[tokio::test]
+async fn artists_are_told_apart_by_their_category() {
+    let mock = MockE621::start().await;
+    Mock::given(method("GET"))
+        .and(path("/tags.json"))
+        .and(query_param("search[name]", "some_artist,pikachu"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "name": "some_artist", "post_count": 12, "category": 1 },
+            { "name": "pikachu", "post_count": 5000, "category": 0 },
+        ])))
+        .mount(&mock.server)
+        .await;
+
+    let tags = tag_api(&mock)
+        .tags(&["some_artist", "pikachu"])
+        .await
+        .unwrap();
+
+    assert_eq!(tags.len(), 2);
+    assert_eq!(tags[0].name, "some_artist");
+    assert!(tags[0].is_artist());
+    assert_eq!(tags[1].name, "pikachu");
+    assert!(!tags[1].is_artist());
+}
+
+#[tokio::test]
+async fn missing_tags_are_left_out() {
+    let mock = MockE621::start().await;
+    // e621 answers with an object instead of an empty list
+    Mock::given(method("GET"))
+        .and(path("/tags.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "tags": [] })))
+        .mount(&mock.server)
+        .await;
+
+    let tags = tag_api(&mock).tags(&["not_a_tag"]).await.unwrap();
+
+    assert!(tags.is_empty());
+}